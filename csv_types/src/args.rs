@@ -0,0 +1,236 @@
+use crate::config::ConfigFileType;
+use argparse::{ArgumentParser, Store, StoreOption, StoreTrue};
+use csv_types_sys::dialect::Dialect;
+use csv_types_sys::encoding::Encoding;
+use csv_types_sys::ErrorPolicy;
+use std::process;
+
+/// Command line options which are shared by all sub commands.
+pub struct CommonArgs {
+    config_file: String,
+    config_file_replace_default: String,
+    has_headers: bool,
+    max_threads: Option<usize>,
+    machine_readable: bool,
+    dialect: String,
+    delimiter: String,
+    quote: String,
+    escape: String,
+    no_double_quote: bool,
+    comment: String,
+    terminator: String,
+    on_error: String,
+    flexible: bool,
+    encoding: String,
+    raw_bytes: bool,
+    cache_size: Option<usize>,
+    null_tokens: bool,
+}
+
+/// Values of the shared command line options after they have been checked.
+pub struct Common {
+    pub config_file: ConfigFileType,
+    pub files: Vec<String>,
+    pub options: csv_types_sys::Options,
+    pub machine_readable: bool,
+    /// Guess the dialect of every file instead of using `options.dialect`.
+    pub sniff_dialect: bool,
+}
+
+impl CommonArgs {
+    pub fn new() -> Self {
+        Self {
+            config_file: String::new(),
+            config_file_replace_default: String::new(),
+            has_headers: false,
+            max_threads: None,
+            machine_readable: false,
+            dialect: String::new(),
+            delimiter: String::new(),
+            quote: String::new(),
+            escape: String::new(),
+            no_double_quote: false,
+            comment: String::new(),
+            terminator: String::new(),
+            on_error: String::new(),
+            flexible: false,
+            encoding: String::new(),
+            raw_bytes: false,
+            cache_size: None,
+            null_tokens: false,
+        }
+    }
+
+    pub fn refer<'a>(&'a mut self, ap: &mut ArgumentParser<'a>) {
+        ap.refer(&mut self.has_headers)
+        .add_option(&["--header"], StoreTrue, "File has header");
+        ap.refer(&mut self.config_file)
+        .add_option(&["-c", "--config-file"], Store, "Add custom types from file");
+        ap.refer(&mut self.config_file_replace_default)
+        .add_option(&["-C", "--config-file-replace-default"], Store, "Same as --config-file but replaces default config");
+        ap.refer(&mut self.max_threads)
+        .add_option(&["--max-threads"], StoreOption, "Maximal thread count (default: number of CPUs)");
+        ap.refer(&mut self.machine_readable)
+        .add_option(&["-m"], StoreTrue, "Machine readable format");
+        ap.refer(&mut self.dialect)
+        .add_option(&["--dialect"], Store, "Use auto to guess delimiter, quoting, line terminator and header");
+        ap.refer(&mut self.on_error)
+        .add_option(&["--on-error"], Store, "What to do with malformed records: fail, skip or collect (default: fail)");
+        ap.refer(&mut self.flexible)
+        .add_option(&["--flexible"], StoreTrue, "Skip and report rows with a different number of fields");
+        ap.refer(&mut self.encoding)
+        .add_option(&["--encoding"], Store, "Encoding of the input like windows-1252 or latin1, auto to guess it (default: utf-8)");
+        ap.refer(&mut self.raw_bytes)
+        .add_option(&["--bytes"], StoreTrue, "Match the raw bytes of each field, invalid UTF-8 included");
+        ap.refer(&mut self.cache_size)
+        .add_option(&["--cache"], StoreOption, "Check repeated values only once, remembering up to this many distinct values per column");
+        ap.refer(&mut self.null_tokens)
+        .add_option(&["--null-tokens"], StoreTrue, "Treat empty values, NA, NULL, - and n/a as missing values");
+        ap.refer(&mut self.delimiter)
+        .add_option(&["-d", "--delimiter"], Store, "Field delimiter (default: ,)");
+        ap.refer(&mut self.quote)
+        .add_option(&["--quote"], Store, "Quote character (default: \")");
+        ap.refer(&mut self.escape)
+        .add_option(&["--escape"], Store, "Escape character for quotes inside of quoted fields");
+        ap.refer(&mut self.no_double_quote)
+        .add_option(&["--no-double-quote"], StoreTrue, "Do not read two quotes as one quote");
+        ap.refer(&mut self.comment)
+        .add_option(&["--comment"], Store, "Ignore lines starting with this character");
+        ap.refer(&mut self.terminator)
+        .add_option(&["--terminator"], Store, "Record terminator (default: CRLF which accepts \\r, \\n and \\r\\n)");
+    }
+
+    pub fn finish(self, files: Vec<String>) -> Common {
+        if !self.config_file.is_empty() && !self.config_file_replace_default.is_empty() {
+            eprintln!("You can only use on of --config-file --config-file-replace-default at a time");
+            process::exit(1);
+        }
+
+        let config_file = if !self.config_file.is_empty() {
+            ConfigFileType::Append(self.config_file)
+        } else if !self.config_file_replace_default.is_empty() {
+            ConfigFileType::ReplaceDefault(self.config_file_replace_default)
+        } else {
+            ConfigFileType::None
+        };
+
+        let sniff_dialect = match &self.dialect[..] {
+            "" => false,
+            "auto" => true,
+            dialect => {
+                eprintln!("Unknown dialect \"{}\", only auto is supported", dialect);
+                process::exit(1);
+            }
+        };
+        let dialect_args = [&self.delimiter, &self.quote, &self.escape, &self.comment, &self.terminator];
+        if sniff_dialect && (self.no_double_quote || dialect_args.iter().any(|arg| !arg.is_empty())) {
+            eprintln!("--dialect auto can not be combined with other dialect options");
+            process::exit(1);
+        }
+
+        let default = Dialect::default();
+        let dialect = Dialect {
+            delimiter: byte_arg("--delimiter", &self.delimiter).unwrap_or(default.delimiter),
+            quote: byte_arg("--quote", &self.quote).unwrap_or(default.quote),
+            escape: byte_arg("--escape", &self.escape),
+            double_quote: !self.no_double_quote,
+            comment: byte_arg("--comment", &self.comment),
+            terminator: match &self.terminator[..] {
+                "" | "CRLF" | "crlf" => csv::Terminator::CRLF,
+                terminator => csv::Terminator::Any(byte_arg("--terminator", terminator).unwrap_or(b'\n')),
+            },
+        };
+
+        let on_error = match &self.on_error[..] {
+            "" | "fail" => ErrorPolicy::Fail,
+            "skip" => ErrorPolicy::Skip,
+            "collect" => ErrorPolicy::Collect,
+            on_error => {
+                eprintln!("Unknown value \"{}\" for --on-error, use fail, skip or collect", on_error);
+                process::exit(1);
+            }
+        };
+
+        let encoding = match &self.encoding[..] {
+            "" => Encoding::Utf8,
+            label => Encoding::for_label(label).unwrap_or_else(|| {
+                eprintln!("Unknown encoding \"{}\"", label);
+                process::exit(1);
+            }),
+        };
+        if self.raw_bytes && encoding != Encoding::Utf8 {
+            eprintln!("--bytes can not be combined with --encoding");
+            process::exit(1);
+        }
+
+        let options = csv_types_sys::Options {
+            has_headers: self.has_headers,
+            max_threads: self.max_threads,
+            dialect,
+            on_error,
+            flexible: self.flexible,
+            encoding,
+            raw_bytes: self.raw_bytes,
+            // More null tokens are read from the config.
+            null_tokens: if self.null_tokens { default_null_tokens() } else { Vec::new() },
+            threshold: None,
+            stats: false,
+            cache_size: self.cache_size,
+        };
+
+        Common {
+            config_file,
+            files: crate::input::paths(files),
+            options,
+            machine_readable: self.machine_readable,
+            sniff_dialect,
+        }
+    }
+}
+
+/// Null tokens of `--null-tokens`.
+fn default_null_tokens() -> Vec<String> {
+    ["", "NA", "NULL", "-", "n/a"].iter().map(|token| token.to_string()).collect()
+}
+
+/// Reads a single byte option. Exits if the value is not exactly one byte.
+fn byte_arg(name: &str, value: &str) -> Option<u8> {
+    match parse_byte(value) {
+        Ok(byte) => byte,
+        Err(()) => {
+            eprintln!("{} must be a single ASCII character, got \"{}\"", name, value);
+            process::exit(1);
+        }
+    }
+}
+
+/// Parses a single ASCII character. `\t`, `\n`, `\r`, `\0` and `tab` are
+/// accepted as well because they are hard to type in a shell.
+fn parse_byte(value: &str) -> Result<Option<u8>, ()> {
+    match value {
+        "" => Ok(None),
+        "\\t" | "tab" => Ok(Some(b'\t')),
+        "\\n" => Ok(Some(b'\n')),
+        "\\r" => Ok(Some(b'\r')),
+        "\\0" => Ok(Some(b'\0')),
+        "\\\\" => Ok(Some(b'\\')),
+        _ if value.len() == 1 && value.is_ascii() => Ok(Some(value.as_bytes()[0])),
+        _ => Err(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_byte_values() {
+        assert_eq!(Ok(None), parse_byte(""));
+        assert_eq!(Ok(Some(b';')), parse_byte(";"));
+        assert_eq!(Ok(Some(b'\t')), parse_byte("\\t"));
+        assert_eq!(Ok(Some(b'\t')), parse_byte("tab"));
+        assert_eq!(Ok(Some(b'\t')), parse_byte("\t"));
+        assert_eq!(Err(()), parse_byte(";;"));
+        assert_eq!(Err(()), parse_byte("ä"));
+    }
+}
@@ -1,29 +1,62 @@
+use csv_types_sys::matcher::{NumberMatcher, ParamMatcher, Temporal, TemporalMatcher};
+use csv_types_sys::native::Native;
 use csv_types_sys::types;
 use std::fs;
 use std::process::exit;
 
-pub fn get_config(config_file: ConfigFileType) -> types::TypeList {
-    match config_file {
-        ConfigFileType::None => types::TypeList::from(default_config()),
-        ConfigFileType::ReplaceDefault(file) => types::TypeList::from(get_config_from_file(&file[..])),
+/// Types and null tokens to use.
+pub struct Config {
+    pub types: types::TypeList,
+    pub null_tokens: Vec<String>,
+}
+
+/// Exits if a type of the config is its own ancestor.
+pub fn get_config(config_file: ConfigFileType) -> Config {
+    let config = match config_file {
+        ConfigFileType::None => Config {
+            types: types::TypeList::from(default_config()),
+            null_tokens: Vec::new(),
+        },
+        ConfigFileType::ReplaceDefault(file) => {
+            let (types, null_tokens) = get_config_from_file(&file[..]);
+            Config { types: types::TypeList::from(types), null_tokens }
+        }
         ConfigFileType::Append(file) => {
             let mut default = default_config();
-            let mut file =  get_config_from_file(&file[..]);
+            let (mut file, null_tokens) = get_config_from_file(&file[..]);
             default.append(&mut file);
-            types::TypeList::from(default)
+            Config { types: types::TypeList::from(default), null_tokens }
         }
+    };
+    if let Err(err) = config.types.check_parents() {
+        eprintln!("{}", err);
+        exit(1);
     }
+    config
 }
 
 fn default_config() -> Vec<types::Type> {
     vec!(
         types::Type::new("string", ".*"),
-        types::Type::new("float", r"[-+]?(?:(?:\d+(?:\.\d*)?)|\.\d+)"),
-        types::Type::new("int", r"[-+]?\d+")
+        types::Type::from_native("float", Native::Float).with_parent("string"),
+        types::Type::from_native("int", Native::Int).with_parent("float"),
+        temporal_type("date", Temporal::Date, "%Y-%m-%d").with_parent("string"),
+        temporal_type("time", Temporal::Time, "%H:%M:%S%.f").with_parent("string"),
+        temporal_type("datetime", Temporal::DateTime, "%Y-%m-%dT%H:%M:%S%.f").with_parent("string"),
+        temporal_type("datetimetz", Temporal::DateTime, "%Y-%m-%dT%H:%M:%S%.f%#z").with_parent("string")
     )
 }
 
-fn get_config_from_file(config_file: &str) -> Vec<types::Type> {
+/// Default types are strict, so codes like `1-2-3` are not taken for dates.
+fn temporal_type(name: &str, temporal: Temporal, format: &str) -> types::Type {
+    types::Type::from_matcher(name, TemporalMatcher::new(temporal, format).unwrap().strict())
+}
+
+/// Reads types from lines like `[name] [pattern]` or `[name]:[parent] [pattern]`
+/// and null tokens from lines like `@null [token]`. `@null` without a token adds
+/// the empty string. Instead of a pattern `@date([format])`, `@time([format])`
+/// or `@datetime([format])` parse the value with a strftime format.
+fn get_config_from_file(config_file: &str) -> (Vec<types::Type>, Vec<String>) {
     let config_file: String = match fs::read_to_string(config_file) {
         Ok(f) => f,
         Err(_) => {
@@ -32,18 +65,77 @@ fn get_config_from_file(config_file: &str) -> Vec<types::Type> {
         }
     };
     let mut list = Vec::new();
+    let mut null_tokens = Vec::new();
     let lines:Vec<&str> = config_file.split('\n').collect();
      
     for line in lines {
+        if line == "@null" {
+            null_tokens.push(String::new());
+            continue;
+        }
+        if let Some(token) = line.strip_prefix("@null ") {
+            null_tokens.push(token.to_owned());
+            continue;
+        }
         let values:Vec<&str> = line.splitn(2, ' ').collect();
         if values.len() == 2 {
-            list.push(types::Type::new(values[0], values[1]));
+            let type_def = match values[0].split_once(':') {
+                Some((name, parent)) => type_definition(name, values[1]).with_parent(parent),
+                None => type_definition(values[0], values[1]),
+            };
+            list.push(type_def);
         }
         
     }
-    list
+    (list, null_tokens)
+}
+
+/// Creates a type from a regex or a directive like `@date(%d.%m.%Y)`,
+/// `@number(,.)` or `@int(0..360)`. `@number` takes the decimal and an optional
+/// grouping separator.
+fn type_definition(name: &str, definition: &str) -> types::Type {
+    let directive = definition.strip_prefix('@').and_then(|d| d.strip_suffix(')')).and_then(|d| d.split_once('('));
+    let temporal = match directive {
+        Some(("date", format)) => (Temporal::Date, format),
+        Some(("time", format)) => (Temporal::Time, format),
+        Some(("datetime", format)) => (Temporal::DateTime, format),
+        Some(("number", separators)) => return number_type(name, separators),
+        Some(("int", params)) | Some(("float", params)) | Some(("varchar", params)) | Some(("decimal", params)) => {
+            return match ParamMatcher::parse(&definition[1..]) {
+                Some(matcher) => types::Type::from_matcher(name, matcher),
+                None => {
+                    eprintln!("Invalid parameters \"{}\" for type {}", params, name);
+                    exit(1);
+                }
+            };
+        }
+        _ => return types::Type::new(name, definition),
+    };
+    match TemporalMatcher::new(temporal.0, temporal.1) {
+        Some(matcher) => types::Type::from_matcher(name, matcher),
+        None => {
+            eprintln!("Invalid format \"{}\" for type {}", temporal.1, name);
+            exit(1);
+        }
+    }
 }
 
+fn number_type(name: &str, separators: &str) -> types::Type {
+    let mut chars = separators.chars();
+    let matcher = match (chars.next(), chars.next(), chars.next()) {
+        (Some(decimal), grouping, None) => NumberMatcher::new(decimal, grouping),
+        _ => None,
+    };
+    match matcher {
+        Some(matcher) => types::Type::from_matcher(name, matcher),
+        None => {
+            eprintln!("Invalid separators \"{}\" for type {}", separators, name);
+            exit(1);
+        }
+    }
+}
+
+#[derive(Clone)]
 pub enum ConfigFileType {
     Append(String),
     ReplaceDefault(String),
@@ -56,7 +148,9 @@ mod tests {
 
     #[test]
     fn get_default_config() {
-        assert_eq!(types::TypeList::from(default_config()), get_config(ConfigFileType::None));
+        let config = get_config(ConfigFileType::None);
+        assert_eq!(types::TypeList::from(default_config()), config.types);
+        assert!(config.null_tokens.is_empty());
     }
 
     #[test]
@@ -66,17 +160,78 @@ mod tests {
             types::Type::new("float", r"\d+.\d+"),
             types::Type::new("bool", "[yn]")
         ));
-        assert_eq!(types.get_types_vec(), get_config(ConfigFileType::ReplaceDefault(String::from("test_data/config"))).get_types_vec());
+        assert_eq!(types.get_types_vec(), get_config(ConfigFileType::ReplaceDefault(String::from("test_data/config"))).types.get_types_vec());
     }
 
     #[test]
     fn get_file_config_merge() {
-        let types = types::TypeList::from(vec!(
-            types::Type::new("string", ".*"),
+        let mut types = default_config();
+        types.retain(|t| t.name != "int" && t.name != "float");
+        types.extend(vec!(
             types::Type::new("int", r"\d+"),
             types::Type::new("float", r"\d+.\d+"),
             types::Type::new("bool", "[yn]")
         ));
-        assert_eq!(types.get_types_vec(), get_config(ConfigFileType::Append(String::from("test_data/config"))).get_types_vec());
+        assert_eq!(&types, get_config(ConfigFileType::Append(String::from("test_data/config"))).types.get_types_vec());
+    }
+
+    #[test]
+    fn get_file_config_null_tokens() {
+        let config = get_config(ConfigFileType::ReplaceDefault(String::from("test_data/config_null")));
+        assert_eq!(vec![String::from("-"), String::new(), String::from("missing value")], config.null_tokens);
+        assert_eq!(1, config.types.get_types_vec().len());
+
+        let config = get_config(ConfigFileType::Append(String::from("test_data/config_null")));
+        assert_eq!(3, config.null_tokens.len());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn get_file_config_parents() {
+        let config = get_config(ConfigFileType::Append(String::from("test_data/config_parent")));
+        let types = config.types.get_types_map();
+        assert_eq!(Some(String::from("int")), types["percent"].parent);
+        assert_eq!(3, config.types.specificity("percent"));
+    }
+
+    #[test]
+    fn sized_types_are_not_default() {
+        let config = get_config(ConfigFileType::None);
+        assert!(config.types.get_types_map().get("u16").is_none());
+        assert!(config.types.get_type("u16").is_some());
+    }
+
+    #[test]
+    fn default_temporal_types_are_strict() {
+        let config = get_config(ConfigFileType::None);
+        let types = config.types.get_types_map();
+        assert!(types["date"].matcher.is_match("2023-01-05"));
+        assert!(!types["date"].matcher.is_match("1-2-3"));
+        assert!(!types["date"].matcher.is_match("99-1-1"));
+        assert!(types["time"].matcher.is_match("10:00:00.5"));
+        assert!(!types["time"].matcher.is_match("1:2:3"));
+        assert!(types["datetime"].matcher.is_match("2023-01-05T10:00:00"));
+        assert!(!types["datetime"].matcher.is_match("2023-1-5T10:00:00"));
+        assert!(types["datetimetz"].matcher.is_match("2023-01-05T10:00:00.25+0200"));
+        assert!(types["datetimetz"].matcher.is_match("2023-01-05T10:00:00Z"));
+        assert!(!types["datetimetz"].matcher.is_match("2023-01-05T10:0:00Z"));
+    }
+
+    #[test]
+    fn get_file_config_temporal_types() {
+        let config = get_config(ConfigFileType::ReplaceDefault(String::from("test_data/config_temporal")));
+        let types = config.types.get_types_map();
+        assert_eq!("date(%d.%m.%Y)", types["day"].matcher.description());
+        assert_eq!(Some(String::from("string")), types["day"].parent);
+        assert!(types["day"].matcher.is_match("29.02.2024"));
+        assert!(!types["day"].matcher.is_match("30.02.2024"));
+        assert!(types["stamp"].matcher.is_match("2024-01-31 23:59:00 +0100"));
+        assert!(!types["clock"].matcher.is_match("24:00"));
+        assert_eq!("number(,.)", types["amount"].matcher.description());
+        assert!(types["amount"].matcher.is_match("1.234,56"));
+        assert!(types["spaced"].matcher.is_match("1 234,5"));
+        assert_eq!("int(0..360)", types["wind"].matcher.description());
+        assert_eq!(Some(String::from("int")), types["wind"].parent);
+        assert!(!types["wind"].matcher.is_match("540"));
+        assert!(types["price"].matcher.is_match("12.50"));
+    }
+}
@@ -0,0 +1,134 @@
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Longest magic number of the supported formats.
+const MAGIC_SIZE: usize = 6;
+
+/// Compression formats which are decompressed while reading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the format from the first bytes of the input. The `BZh` of
+    /// bzip2 has to be followed by the block size from 1 to 9.
+    fn from_magic(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if matches!(magic, [b'B', b'Z', b'h', b'1'..=b'9', ..]) {
+            Some(Self::Bzip2)
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::Xz)
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Detects the format from the extension of the file name.
+    fn from_extension(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?;
+        match &extension.to_ascii_lowercase()[..] {
+            "gz" | "gzip" => Some(Self::Gzip),
+            "bz2" | "bzip2" => Some(Self::Bzip2),
+            "xz" => Some(Self::Xz),
+            "zst" | "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Wraps the input in a decoder if it is compressed.
+///
+/// The format is detected from the magic bytes at the start of the input and
+/// from the extension of `path` if they are not known. Uncompressed input is
+/// returned unchanged. Concatenated streams like `cat a.gz b.gz` are read
+/// completely.
+pub fn decompress<'a>(
+    mut input: Box<dyn Read + 'a>,
+    path: &str,
+) -> io::Result<(Box<dyn Read + 'a>, Option<Compression>)> {
+    let mut magic = Vec::with_capacity(MAGIC_SIZE);
+    input
+        .by_ref()
+        .take(MAGIC_SIZE as u64)
+        .read_to_end(&mut magic)?;
+    let compression = Compression::from_magic(&magic).or_else(|| Compression::from_extension(path));
+    let input: Box<dyn Read + 'a> = Box::new(io::Cursor::new(magic).chain(input));
+
+    let input: Box<dyn Read + 'a> = match compression {
+        None => input,
+        Some(Compression::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(input)),
+        Some(Compression::Bzip2) => Box::new(bzip2::read::MultiBzDecoder::new(input)),
+        Some(Compression::Xz) => Box::new(xz2::read::XzDecoder::new_multi_decoder(input)),
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::new(input)?),
+    };
+    Ok((input, compression))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const CSV: &str = "a,b\n1,2\n";
+
+    fn read(input: Vec<u8>, path: &str) -> (String, Option<Compression>) {
+        let (mut read, compression) = decompress(Box::new(io::Cursor::new(input)), path).unwrap();
+        let mut csv = String::new();
+        read.read_to_string(&mut csv).unwrap();
+        (csv, compression)
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decompress_by_magic_bytes() {
+        let gz = gzip(CSV.as_bytes());
+        assert_eq!((CSV.to_owned(), Some(Compression::Gzip)), read(gz, "-"));
+
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(CSV.as_bytes()).unwrap();
+        let bz2 = encoder.finish().unwrap();
+        assert_eq!((CSV.to_owned(), Some(Compression::Bzip2)), read(bz2, "-"));
+
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(CSV.as_bytes()).unwrap();
+        let xz = encoder.finish().unwrap();
+        assert_eq!((CSV.to_owned(), Some(Compression::Xz)), read(xz, "-"));
+
+        let zst = zstd::encode_all(CSV.as_bytes(), 0).unwrap();
+        assert_eq!((CSV.to_owned(), Some(Compression::Zstd)), read(zst, "-"));
+    }
+
+    #[test]
+    fn decompress_concatenated_streams() {
+        let mut gz = gzip(b"a,b\n");
+        gz.extend(gzip(b"1,2\n"));
+        assert_eq!(CSV, read(gz, "data.csv.gz").0);
+    }
+
+    #[test]
+    fn keep_uncompressed_input() {
+        assert_eq!((CSV.to_owned(), None), read(CSV.as_bytes().to_vec(), "data.csv"));
+        assert_eq!((String::from("a"), None), read(b"a".to_vec(), "-"));
+        assert_eq!((String::from("BZh,x\n1,2\n"), None), read(b"BZh,x\n1,2\n".to_vec(), "-"));
+    }
+
+    #[test]
+    fn compression_from_extension() {
+        assert_eq!(Some(Compression::Gzip), Compression::from_extension("data.csv.GZ"));
+        assert_eq!(Some(Compression::Zstd), Compression::from_extension("dir/data.zst"));
+        assert_eq!(None, Compression::from_extension("data.csv"));
+        assert_eq!(None, Compression::from_extension("-"));
+    }
+}
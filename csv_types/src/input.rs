@@ -0,0 +1,68 @@
+use crate::args::Common;
+use crate::decompress;
+use crate::print_result;
+use csv_types_sys::encoding::{self, Encoding};
+use csv_types_sys::{dialect, CsvInput, Options};
+use std::fs::File;
+use std::io;
+
+/// Name used on the command line to read from stdin.
+pub const STDIN: &str = "-";
+
+/// Opens the given file or stdin if the path is `-`.
+pub fn open(path: &str) -> io::Result<Box<dyn io::Read>> {
+    if path == STDIN {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    Ok(Box::new(File::open(path)?))
+}
+
+/// Opens, decompresses and decodes the input and guesses its dialect if
+/// `--dialect auto` is used.
+///
+/// Returns the options to use for this input. A header is assumed if
+/// `--header` is given or the header has been guessed. The error is a message
+/// which names the input.
+pub fn csv_input(path: &str, common: &Common) -> Result<(CsvInput<'static>, Options), String> {
+    let can_not_read = |err: io::Error| format!("Can not read \"{}\": {}", path, err);
+    let mut options = common.options.clone();
+    let (input, _) = open(path)
+        .and_then(|input| decompress::decompress(input, path))
+        .map_err(can_not_read)?;
+    let (input, name) = encoding::decode(input, options.encoding).map_err(can_not_read)?;
+    if options.encoding == Encoding::Auto {
+        print_result::encoding(name);
+    }
+    let input = CsvInput::Read(input);
+    // The dialect is guessed on the decoded input.
+    options.encoding = Encoding::Utf8;
+    if !common.sniff_dialect {
+        return Ok((input, options));
+    }
+
+    let (sniffed, input) = dialect::sniff_input(input).map_err(can_not_read)?;
+    print_result::sniffed(&sniffed);
+    options.dialect = sniffed.dialect;
+    options.has_headers |= sniffed.has_headers;
+    Ok((input, options))
+}
+
+/// Returns the inputs to read. Without any file stdin is used.
+pub fn paths(files: Vec<String>) -> Vec<String> {
+    if files.is_empty() {
+        vec![String::from(STDIN)]
+    } else {
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_default_to_stdin() {
+        assert_eq!(vec![String::from("-")], paths(Vec::new()));
+        assert_eq!(vec![String::from("a.csv")], paths(vec![String::from("a.csv")]));
+    }
+}
@@ -1,7 +1,9 @@
-use std::{io, io::prelude::*};
+mod args;
 mod print_result;
 mod sub_commands;
 mod config;
+mod decompress;
+mod input;
 
 fn main() {
     let sub_commands:[Box<dyn sub_commands::SubCommand>; 2] = [
@@ -28,16 +30,3 @@ fn main() {
     }
 
 }
-
-fn read_input_from_stdin() -> String{
-    let mut input = String::new();
-    for line in io::stdin().lock().lines() {
-        let line = match line {
-            Ok(e) => e,
-            Err(_) => continue
-        };
-        input.push_str(&line[..]);
-        input.push('\n');
-    }
-    input
-}
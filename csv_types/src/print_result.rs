@@ -1,79 +1,306 @@
-use csv_types_sys::types;
+use csv_types_sys::dialect;
+use std::fmt::Write;
+use csv_types_sys::{Column, ColumnStats, ErrorPolicy, FailedRow, MalformedRecord, RaggedRecord};
 
-pub fn matching_types(types: &[Vec<types::Type>], headers: &[String], machine_readable: bool) {
+pub fn section(name: &str, machine_readable: bool) {
     if machine_readable {
-        matching_types_machine_readable(types);
+        println!("#{}", name);
     } else {
-        matching_types_human_readable(types, headers);
+        println!("==> {} <==", name);
     }
 }
 
-fn matching_types_human_readable(types: &[Vec<types::Type>], headers: &[String]) {
+/// Reports a guessed dialect on stderr so the result stays readable by machines.
+pub fn sniffed(sniffed: &dialect::Sniffed) {
+    let dialect = &sniffed.dialect;
+    let line_ending = match sniffed.line_ending {
+        dialect::LineEnding::CrLf => "\\r\\n",
+        dialect::LineEnding::Lf => "\\n",
+        dialect::LineEnding::Cr => "\\r",
+    };
+    eprintln!(
+        "Detected dialect: delimiter {}, quote {}, escape {}, line terminator {}, header {}",
+        byte_name(dialect.delimiter),
+        byte_name(dialect.quote),
+        dialect.escape.map_or(String::from("none"), byte_name),
+        line_ending,
+        if sniffed.has_headers { "yes" } else { "no" }
+    );
+}
+
+pub fn encoding(name: &str) {
+    eprintln!("Detected encoding: {}", name);
+}
+
+fn byte_name(byte: u8) -> String {
+    match byte {
+        b'\t' => String::from("\\t"),
+        byte => format!("{}", byte as char),
+    }
+}
+
+/// Prints the types of every column from the most to the least specific. With
+/// `best_only` only the most specific type is printed.
+pub fn matching_types(columns: &[Column], headers: &[String], machine_readable: bool, best_only: bool) {
+    if machine_readable {
+        matching_types_machine_readable(columns, best_only);
+    } else {
+        matching_types_human_readable(columns, headers, best_only);
+    }
+}
+
+fn matching_types_human_readable(columns: &[Column], headers: &[String], best_only: bool) {
+    print!("{}", matching_types_table(columns, headers, best_only));
+}
+
+fn matching_types_table(columns: &[Column], headers: &[String], best_only: bool) -> String {
+    let mut table = String::new();
+    let names = columns.iter().map(|col| {
+        let count = if best_only { 1 } else { col.types.len() };
+        col.types.iter().take(count).enumerate().map(|(i, t)| {
+            let ratio = col.match_ratio(i);
+            let name = if ratio < 1.0 { format!("{} ({:.1}%)", t, ratio * 100.0) } else { t.to_string() };
+            // The best type is marked with a *.
+            if i == 0 && !best_only { format!("*{}", name) } else { name }
+        }).collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+    let nulls = columns.iter().map(|col| if col.nulls > 0 { format!("{:.1}% null", col.null_ratio() * 100.0) } else { String::new() }).collect::<Vec<_>>();
+    let distinct = columns.iter().map(|col| col.distinct.map_or(String::new(), |distinct| format!("{} distinct", distinct))).collect::<Vec<_>>();
     let mut width = Vec::new();
-    let mut count = Vec::new();
     let mut max_rows = 0; 
-    for t1 in types.iter() {
-        let mut w = 0;
+    for ((t1, null), distinct) in names.iter().zip(&nulls).zip(&distinct) {
+        let mut w = null.len().max(distinct.len());
         if max_rows < t1.len() {
             max_rows = t1.len();
         }
-        count.push(t1.len());
         for t in t1 {
-            if w < t.name.len() {
-                w = t.name.len();
+            if w < t.len() {
+                w = t.len();
             }
         }
         width.push(w);
     }
 
+    // Without rows there are headers but no columns.
     for (index, header) in headers.iter().enumerate() {
-        let max_width = width[index];
-        if max_width < header.len() {
-            width[index] = header.len();
+        match width.get_mut(index) {
+            Some(max_width) if *max_width < header.len() => *max_width = header.len(),
+            Some(_) => {}
+            None => width.push(header.len()),
         }
     }
 
     if !headers.is_empty() {
         let mut complete_width = 0;
         for (col_id, header) in headers.iter().enumerate() {
-            let col_width = match width.get(col_id) {
-                Some(w) => w,
-                None => &(10 as usize)
-            };
-            print!("| {name:>width$} ", width=col_width, name=header);
+            let col_width = width.get(col_id).unwrap_or(&10);
+            write!(table, "| {name:>width$} ", width=col_width, name=header).unwrap();
             complete_width += 3 + col_width;
         }
-        println!("|");
+        writeln!(table, "|").unwrap();
 
-        println!("{:=>width$}", "", width=complete_width+1);
+        writeln!(table, "{:=>width$}", "", width=complete_width+1).unwrap();
     }
 
     for row in 0..max_rows {
-        for (col_id, col) in types.iter().enumerate() {
-            let col_width = match width.get(col_id) {
-                Some(w) => w,
-                None => &(10 as usize)
-            };
+        for (col_id, col) in names.iter().enumerate() {
+            let col_width = width.get(col_id).unwrap_or(&10);
             let name = match col.get(row) {
-                Some(t) => &t.name,
+                Some(t) => t,
                 None => ""
             };
-            print!("| {name:>width$} ", width=col_width, name=name);
+            write!(table, "| {name:>width$} ", width=col_width, name=name).unwrap();
+        }
+        writeln!(table, "|").unwrap();
+    }
+
+    for extra_row in [&nulls, &distinct] {
+        if extra_row.iter().any(|value| !value.is_empty()) {
+            for (col_id, value) in extra_row.iter().enumerate() {
+                let col_width = width.get(col_id).unwrap_or(&10);
+                write!(table, "| {name:>width$} ", width=col_width, name=value).unwrap();
+            }
+            writeln!(table, "|").unwrap();
         }
-        println!("|");
     }
+    table
 }
 
-fn matching_types_machine_readable(types: &[Vec<types::Type>]) {
-    for row in types {
-        for t in row {
-            print!("{},", t.name);
+fn matching_types_machine_readable(columns: &[Column], best_only: bool) {
+    for col in columns {
+        let count = if best_only { 1 } else { col.types.len() };
+        for t in col.types.iter().take(count) {
+            print!("{},", t);
         }
         println!();
     }
+    for (col_id, col) in columns.iter().enumerate() {
+        if col.nulls > 0 {
+            println!("nulls:{}:{}:{}", col_id, col.nulls, col.values);
+        }
+        if let Some(distinct) = col.distinct {
+            println!("distinct:{}:{}", col_id, distinct);
+        }
+        for (index, t) in col.types.iter().enumerate() {
+            if col.match_ratio(index) < 1.0 {
+                println!("ratio:{}:{}:{}:{}", col_id, t, col.matched[index], col.values - col.nulls);
+            }
+        }
+    }
+}
+
+/// Prints the values which did not match types which are shown because of
+/// `--threshold`.
+pub fn outliers(columns: &[Column], headers: &[String], machine_readable: bool) {
+    let mut outliers = columns.iter().flat_map(|col| &col.outliers).collect::<Vec<_>>();
+    outliers.sort_by_key(|outlier| (outlier.row, outlier.column));
+    if outliers.is_empty() {
+        return;
+    }
+    if !machine_readable {
+        eprintln!("These values did not match all types: ");
+    }
+    for outlier in outliers {
+        let header = headers.get(outlier.column);
+        if machine_readable {
+            println!("outlier:{}:{}:{}:{}:{}:{}:{}", outlier.line, outlier.record, outlier.byte, outlier.column, header.map_or("", |h| h.as_str()), outlier.types.join(","), outlier.value);
+        } else {
+            let header = header.map_or(String::new(), |header| format!(" ({})", header));
+            println!("line {} (record {}, byte {}): column {}{}: {:?} is not {}", outlier.line, outlier.record, outlier.byte, outlier.column, header, outlier.value, outlier.types.join(", "));
+        }
+    }
+}
+
+/// Output format of `--stats`.
+#[derive(Clone, Copy)]
+pub enum StatsFormat {
+    Human,
+    Json,
+    Csv,
+}
+
+/// Prints the statistics of every column. JSON and CSV are printed on their
+/// own so they can be read by other tools.
+pub fn stats(columns: &[Column], headers: &[String], format: StatsFormat) {
+    match format {
+        StatsFormat::Human => stats_human_readable(columns, headers),
+        StatsFormat::Json => stats_json(columns, headers),
+        StatsFormat::Csv => stats_csv(columns, headers),
+    }
+}
+
+fn distinct(stats: &ColumnStats) -> String {
+    if stats.distinct_overflow { format!("{}+", stats.distinct) } else { stats.distinct.to_string() }
 }
 
-pub fn assert_types(rows: &[(usize, Vec<usize>)], machine_readable: bool) {
+fn stats_human_readable(columns: &[Column], headers: &[String]) {
+    for (col_id, col) in columns.iter().enumerate() {
+        let stats = match &col.stats {
+            Some(stats) => stats,
+            None => continue,
+        };
+        let header = headers.get(col_id).map_or(String::new(), |header| format!(" ({})", header));
+        let length = match (stats.min_length, stats.max_length) {
+            (Some(min), Some(max)) => format!(", length {}-{}", min, max),
+            _ => String::new(),
+        };
+        println!("column {}{}: {} values, {} null, {} empty, {} distinct{}", col_id, header, col.values, col.nulls, stats.empties, distinct(stats), length);
+        for type_stats in &stats.types {
+            print!("  {}: {} of {} ({:.1}%)", type_stats.name, type_stats.matched, col.values - col.nulls, col.ratio(type_stats.matched) * 100.0);
+            match &type_stats.first_failure {
+                Some(failure) => println!(", first failure on line {}: {:?}", failure.line, failure.value),
+                None => println!(),
+            }
+        }
+    }
+}
+
+fn stats_json(columns: &[Column], headers: &[String]) {
+    let columns = columns.iter().enumerate().filter_map(|(col_id, col)| {
+        let stats = col.stats.as_ref()?;
+        let types = stats.types.iter().map(|type_stats| {
+            let first_failure = type_stats.first_failure.as_ref().map_or(String::from("null"), |failure| {
+                format!("{{\"line\":{},\"record\":{},\"byte\":{},\"value\":{}}}", failure.line, failure.record, failure.byte, json_string(&failure.value))
+            });
+            let reported = col.types.iter().any(|t| t.name == type_stats.name);
+            format!("{{\"name\":{},\"matched\":{},\"ratio\":{},\"reported\":{},\"first_failure\":{}}}", json_string(&type_stats.name), type_stats.matched, col.ratio(type_stats.matched), reported, first_failure)
+        }).collect::<Vec<_>>();
+        let optional = |value: Option<usize>| value.map_or(String::from("null"), |value| value.to_string());
+        Some(format!(
+            "{{\"column\":{},\"header\":{},\"values\":{},\"nulls\":{},\"empties\":{},\"distinct\":{},\"distinct_overflow\":{},\"min_length\":{},\"max_length\":{},\"types\":[{}]}}",
+            col_id,
+            headers.get(col_id).map_or(String::from("null"), |header| json_string(header)),
+            col.values,
+            col.nulls,
+            stats.empties,
+            stats.distinct,
+            stats.distinct_overflow,
+            optional(stats.min_length),
+            optional(stats.max_length),
+            types.join(",")
+        ))
+    }).collect::<Vec<_>>();
+    println!("[{}]", columns.join(","));
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// One row per column and type.
+fn stats_csv(columns: &[Column], headers: &[String]) {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    let result = (|| {
+        writer.write_record(["column", "header", "values", "nulls", "empties", "distinct", "distinct_overflow", "min_length", "max_length", "type", "matched", "ratio", "reported", "first_failure_line", "first_failure_value"])?;
+        for (col_id, col) in columns.iter().enumerate() {
+            let stats = match &col.stats {
+                Some(stats) => stats,
+                None => continue,
+            };
+            let optional = |value: Option<usize>| value.map_or(String::new(), |value| value.to_string());
+            for type_stats in &stats.types {
+                let failure = type_stats.first_failure.as_ref();
+                writer.write_record([
+                    col_id.to_string(),
+                    headers.get(col_id).cloned().unwrap_or_default(),
+                    col.values.to_string(),
+                    col.nulls.to_string(),
+                    stats.empties.to_string(),
+                    stats.distinct.to_string(),
+                    stats.distinct_overflow.to_string(),
+                    optional(stats.min_length),
+                    optional(stats.max_length),
+                    type_stats.name.clone(),
+                    type_stats.matched.to_string(),
+                    col.ratio(type_stats.matched).to_string(),
+                    col.types.iter().any(|t| t.name == type_stats.name).to_string(),
+                    failure.map_or(String::new(), |failure| failure.line.to_string()),
+                    failure.map_or(String::new(), |failure| failure.value.clone()),
+                ])?;
+            }
+        }
+        writer.flush().map_err(csv::Error::from)
+    })();
+    if let Err(err) = result {
+        eprintln!("Could not write the statistics: {}", err);
+    }
+}
+
+pub fn assert_types(rows: &[FailedRow], machine_readable: bool) {
     if machine_readable {
         assert_types_machine_readable(rows)
     } else {
@@ -81,29 +308,92 @@ pub fn assert_types(rows: &[(usize, Vec<usize>)], machine_readable: bool) {
     }
 }
 
-fn assert_types_human_readable(rows:&[(usize, Vec<usize>)]) {
+fn assert_types_human_readable(rows: &[FailedRow]) {
     if !rows.is_empty() {
         eprintln!("These rows did not match: ");
-        for failed_assertions in rows {
-            print!("{}", failed_assertions.0);
-            for col in &failed_assertions.1 {
-                print!(":{}", col);
+        for failed_row in rows {
+            println!("line {} (record {}, byte {}):", failed_row.line, failed_row.record, failed_row.byte);
+            for mismatch in &failed_row.mismatches {
+                let header = mismatch.header.as_ref().map_or(String::new(), |header| format!(" ({})", header));
+                println!("  column {}{}: {:?} is not {}", mismatch.column, header, mismatch.value, mismatch.expected);
             }
-            println!();
         }
     } else {
         eprintln!("All rows matched");
     }
 }
 
-fn assert_types_machine_readable(rows:&[(usize, Vec<usize>)]) {
-    if !rows.is_empty() {
-        for failed_assertions in rows {
-            print!("{}", failed_assertions.0);
-            for col in &failed_assertions.1 {
-                print!(":{}", col);
+fn assert_types_machine_readable(rows: &[FailedRow]) {
+    for failed_row in rows {
+        for mismatch in &failed_row.mismatches {
+            println!(
+                "{}:{}:{}:{}:{}:{}:{}",
+                failed_row.line,
+                failed_row.record,
+                failed_row.byte,
+                mismatch.column,
+                mismatch.header.as_deref().unwrap_or(""),
+                mismatch.expected,
+                mismatch.value
+            );
+        }
+    }
+}
+
+pub fn malformed(records: &[MalformedRecord], policy: ErrorPolicy, machine_readable: bool) {
+    match policy {
+        ErrorPolicy::Fail => {},
+        ErrorPolicy::Skip => {
+            for record in records {
+                eprintln!("Skipped malformed record {} (line {}, byte {}): {}", record.record, record.line, record.byte, record.kind);
             }
-            println!();
+        },
+        ErrorPolicy::Collect if machine_readable => malformed_machine_readable(records),
+        ErrorPolicy::Collect => malformed_human_readable(records),
+    }
+}
+
+fn malformed_human_readable(records: &[MalformedRecord]) {
+    if !records.is_empty() {
+        eprintln!("These records could not be parsed: ");
+        for record in records {
+            println!("record {} (line {}, byte {}): {}", record.record, record.line, record.byte, record.kind);
         }
     }
 }
+
+fn malformed_machine_readable(records: &[MalformedRecord]) {
+    for record in records {
+        println!("malformed:{}:{}:{}:{}", record.record, record.line, record.byte, record.kind);
+    }
+}
+
+pub fn ragged(records: &[RaggedRecord], machine_readable: bool) {
+    if machine_readable {
+        for record in records {
+            println!("ragged:{}:{}:{}:{}:{}", record.record, record.line, record.byte, record.expected, record.found);
+        }
+    } else if !records.is_empty() {
+        eprintln!("These records have the wrong number of fields: ");
+        for record in records {
+            println!("record {} (line {}, byte {}): expected {} fields, found {}", record.record, record.line, record.byte, record.expected, record.found);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_headers_without_rows() {
+        let table = matching_types_table(&[], &[String::from("h1"), String::from("h2")], false);
+        assert_eq!("| h1 | h2 |\n===========\n", table);
+    }
+
+    #[test]
+    fn escape_json_strings() {
+        assert_eq!(r#""a\"b\\c\n\u0001ü""#, json_string("a\"b\\c\n\u{1}ü"));
+    }
+}
@@ -3,20 +3,19 @@ use csv_types_sys;
 use csv_types_sys::types;
 use crate::print_result;
 use std::process;
-use crate::config::ConfigFileType;
-use argparse::{ArgumentParser, StoreTrue, StoreOption, Store};
+use crate::args::{Common, CommonArgs};
+use argparse::{ArgumentParser, Store, List};
 
 
 
 impl AssertTypes {
-    fn assert_types(csv: &str, type_list: types::TypeList, options: csv_types_sys::Options, asserted_types: String, machine_readable: bool) {
-        let types_map = type_list.get_types_map();
+    fn expected_types(type_list: &types::TypeList, asserted_types: &str) -> Vec<types::Type> {
         let mut expected_types = Vec::new();
-        for type_name in asserted_types.split(',') {
+        for type_name in Self::split_types(asserted_types) {
            
             let type_name = type_name.trim();
-            let expected = match types_map.get(type_name) {
-                Some(t) => t.clone(),
+            let expected = match type_list.get_type(type_name) {
+                Some(t) => t,
                 None => {
                     eprintln!("The type {} is not defined", type_name);
                     process::exit(1);
@@ -24,71 +23,72 @@ impl AssertTypes {
             };
             expected_types.push(expected);
         }
-        
-        let rows = match csv_types_sys::assert_columns_match(csv_types_sys::CsvInput::Csv(csv), expected_types, options) {
-            Ok(rows) => rows,
+        expected_types
+    }
+
+    /// Splits the type list at commas which are not inside parentheses, so
+    /// `decimal(10,2),int` gives two types.
+    fn split_types(asserted_types: &str) -> Vec<&str> {
+        let mut type_names = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (index, c) in asserted_types.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    type_names.push(&asserted_types[start..index]);
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        type_names.push(&asserted_types[start..]);
+        type_names
+    }
+
+    /// Returns `false` if the file could not be checked.
+    fn assert_types(csv: csv_types_sys::CsvInput, expected_types: Vec<types::Type>, options: csv_types_sys::Options, machine_readable: bool) -> bool {
+        let on_error = options.on_error;
+        let result = match csv_types_sys::assert_columns_match(csv, expected_types, options) {
+            Ok(result) => result,
             Err(err) => {
                 match err {
                     csv_types_sys::Error::Join => eprintln!("Could not join threads."),
                     csv_types_sys::Error::ThreadCount => eprintln!("The thread count must be bigger than 0"),
-                    csv_types_sys::Error::ColumnCountNotMatching => eprintln!("The given number of types does not match the number of columns"),
+                    csv_types_sys::Error::ColumnCountNotMatching { expected, found } => eprintln!("The given number of types ({}) does not match the number of columns ({})", expected, found),
+                    csv_types_sys::Error::Malformed(_) | csv_types_sys::Error::Read(_) | csv_types_sys::Error::ParentCycle(_) => eprintln!("{}", err),
                 }
-                process::exit(1);
+                return false;
             }
         };
     
-        print_result::assert_types(&rows[..], machine_readable);
-    
+        print_result::assert_types(&result.failed_rows[..], machine_readable);
+        print_result::malformed(&result.malformed, on_error, machine_readable);
+        print_result::ragged(&result.ragged, machine_readable);
+        true
     }
 
-    fn setup_args(args: Vec<String>) -> (ConfigFileType, String, csv_types_sys::Options, bool) {
-        let mut config_file_replace_default = String::new();
-        let mut config_file = String::new();
-        let mut options =  csv_types_sys::Options {
-            has_headers: false,
-            max_threads: None
-        };
+    fn setup_args(args: Vec<String>) -> (Common, String) {
+        let mut common = CommonArgs::new();
+        let mut files = Vec::new();
         let mut assert = String::new();
-        let mut machine_readable = false;
     
         let mut ap = ArgumentParser::new();
-        ap.refer(&mut options.has_headers)
-        .add_option(&["--header"], StoreTrue, "File has header");
-        ap.refer(&mut config_file)
-        .add_option(&["-c", "--config-file"], Store, "Add custom types from file");
-        ap.refer(&mut config_file_replace_default)
-        .add_option(&["-C", "--config-file-replace-default"], Store, "Same as --config-file but replaces default config");
-        ap.refer(&mut options.max_threads)
-        .add_option(&["--max-threads"], StoreOption, "Maximal thread count");
-        ap.refer(&mut machine_readable)
-        .add_option(&["-m"], StoreTrue, "Machine readable format");
+        common.refer(&mut ap);
         ap.refer(&mut assert)
-        .add_argument("Expected types", Store, "Expected types comma seperated");
-        
+        .add_argument("Expected types", Store, "Expected types comma seperated")
+        .required();
+        ap.refer(&mut files)
+        .add_argument("Files", List, "CSV files to check, - reads from stdin (default: -)");
         
         ap.parse(args, &mut std::io::stdout(), &mut std::io::stderr())
             .map_err(|c| std::process::exit(c))
             .ok();
         
-        
-        
         drop(ap);
     
-        if !config_file.is_empty() && !config_file_replace_default.is_empty() {
-            eprintln!("You can only use on of --config-file --config-file-replace-default at a time");
-            process::exit(1);
-        }
-    
-        let config_file = if !config_file.is_empty() {
-            ConfigFileType::Append(config_file)
-        } else if !config_file_replace_default.is_empty() {
-            ConfigFileType::ReplaceDefault(config_file_replace_default)
-        } else {
-            ConfigFileType::None
-        };
-    
-        (config_file, assert, options, machine_readable)
-        
+        (common.finish(files), assert)
     }
 }
 
@@ -99,10 +99,39 @@ impl super::SubCommand for AssertTypes {
     }
 
     fn run(&self, args: Vec<String>) {
-        let (config_file, asserted_types, options, machine_readable) = Self::setup_args(args);
-        let csv = crate::read_input_from_stdin();
-        let type_list = crate::config::get_config(config_file);
-        Self::assert_types(&csv, type_list, options, asserted_types, machine_readable);
+        let (mut common, asserted_types) = Self::setup_args(args);
+        let mut config = crate::config::get_config(common.config_file.clone());
+        common.options.null_tokens.append(&mut config.null_tokens);
+        let expected_types = Self::expected_types(&config.types, &asserted_types);
+        // A file which can not be checked does not stop the others.
+        let mut failed = false;
+        for file in &common.files {
+            if common.files.len() > 1 {
+                print_result::section(file, common.machine_readable);
+            }
+            let checked = match crate::input::csv_input(file, &common) {
+                Ok((csv, options)) => Self::assert_types(csv, expected_types.clone(), options, common.machine_readable),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    false
+                }
+            };
+            failed |= !checked;
+        }
+        if failed {
+            process::exit(1);
+        }
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_types_with_parameters() {
+        assert_eq!(vec!["int", " decimal(10,2)", "float?"], AssertTypes::split_types("int, decimal(10,2),float?"));
+        assert_eq!(vec!["string"], AssertTypes::split_types("string"));
+    }
+}
@@ -2,73 +2,87 @@ use csv_types_sys;
 use csv_types_sys::types;
 use crate::print_result;
 use std::process;
-use crate::config::ConfigFileType;
-use argparse::{ArgumentParser, StoreTrue, StoreOption, Store};
+use crate::args::{Common, CommonArgs};
+use argparse::{ArgumentParser, List, Store, StoreOption, StoreTrue};
 
 pub struct MatchingTypes;
 
+/// Command line options which only `match` has.
+struct MatchArgs {
+    best_only: bool,
+    /// Percentage of values a type has to match.
+    threshold: Option<f64>,
+    stats: String,
+    stats_format: Option<print_result::StatsFormat>,
+    date_formats: bool,
+    number_locales: bool,
+    sized_types: bool,
+}
+
 impl MatchingTypes {
-    fn matching_types(input: &str, type_list: types::TypeList, options: csv_types_sys::Options, machine_readable: bool) {
-        let (headers, types) = match csv_types_sys::get_types(csv_types_sys::CsvInput::Csv(input), type_list, options) {
+    /// Returns `false` if the file could not be checked.
+    fn matching_types(input: csv_types_sys::CsvInput, type_list: types::TypeList, options: csv_types_sys::Options, machine_readable: bool, match_args: &MatchArgs) -> bool {
+        let on_error = options.on_error;
+        let result = match csv_types_sys::get_types(input, type_list, options) {
             Ok(r) => r,
             Err(err) => {
                 match err {
                     csv_types_sys::Error::Join => eprintln!("Could not join threads."),
                     csv_types_sys::Error::ThreadCount => eprintln!("The thread count must be bigger than 0"),
+                    csv_types_sys::Error::Malformed(_) | csv_types_sys::Error::Read(_) | csv_types_sys::Error::ParentCycle(_) => eprintln!("{}", err),
                     _ => eprintln!("An unknown Error accoured")
                 }
-                process::exit(1);
+                return false;
             }
         };
     
-        print_result::matching_types(&types, &headers, machine_readable);
+        match match_args.stats_format {
+            // Only the statistics so the output stays valid JSON or CSV.
+            Some(format @ print_result::StatsFormat::Json) | Some(format @ print_result::StatsFormat::Csv) => {
+                print_result::stats(&result.columns, &result.headers, format);
+                return true;
+            }
+            Some(print_result::StatsFormat::Human) => {
+                print_result::matching_types(&result.columns, &result.headers, machine_readable, match_args.best_only);
+                print_result::stats(&result.columns, &result.headers, print_result::StatsFormat::Human);
+            }
+            None => print_result::matching_types(&result.columns, &result.headers, machine_readable, match_args.best_only),
+        }
+        print_result::outliers(&result.columns, &result.headers, machine_readable);
+        print_result::malformed(&result.malformed, on_error, machine_readable);
+        print_result::ragged(&result.ragged, machine_readable);
+        true
     }
 
-    fn setup_args(args: Vec<String>) -> (ConfigFileType, csv_types_sys::Options, bool) {
-        println!("{:#?}", args);
-        let mut config_file_replace_default = String::new();
-        let mut config_file = String::new();
-        let mut options =  csv_types_sys::Options {
-            has_headers: false,
-            max_threads: None
-        };
-
-        let mut machine_readable = false;
+    fn setup_args(args: Vec<String>) -> (Common, MatchArgs) {
+        let mut common = CommonArgs::new();
+        let mut files = Vec::new();
+        let mut match_args = MatchArgs { best_only: false, threshold: None, stats: String::new(), stats_format: None, date_formats: false, number_locales: false, sized_types: false };
     
         let mut ap = ArgumentParser::new();
-        ap.refer(&mut options.has_headers)
-        .add_option(&["--header"], StoreTrue, "File has header");
-        ap.refer(&mut config_file)
-        .add_option(&["-c", "--config-file"], Store, "Add custom types from file");
-        ap.refer(&mut config_file_replace_default)
-        .add_option(&["-C", "--config-file-replace-default"], Store, "Same as --config-file but replaces default config");
-        ap.refer(&mut options.max_threads)
-        .add_option(&["--max-threads"], StoreOption, "Maximal thread count");
-        ap.refer(&mut machine_readable)
-        .add_option(&["-m"], StoreTrue, "Machine readable format");        
+        common.refer(&mut ap);
+        ap.refer(&mut match_args.best_only)
+        .add_option(&["--best"], StoreTrue, "Only show the most specific type of each column");
+        ap.refer(&mut match_args.threshold)
+        .add_option(&["--threshold"], StoreOption, "Show types which match at least this percentage of the values and list the values which do not match");
+        ap.refer(&mut match_args.stats)
+        .add_option(&["--stats"], Store, "Show statistics of every column: human, json or csv");
+        ap.refer(&mut match_args.date_formats)
+        .add_option(&["--date-formats"], StoreTrue, "Show which of the common date formats fit each column");
+        ap.refer(&mut match_args.number_locales)
+        .add_option(&["--number-locales"], StoreTrue, "Show which decimal and grouping separator conventions fit each numeric column");
+        ap.refer(&mut match_args.sized_types)
+        .add_option(&["--sized-types"], StoreTrue, "Show which of i8 to u64, f32 and f64 fit each numeric column");
+        ap.refer(&mut files)
+        .add_argument("Files", List, "CSV files to check, - reads from stdin (default: -)");
         
         ap.parse(args, &mut std::io::stdout(), &mut std::io::stderr())
             .map_err(|c| std::process::exit(c))
             .ok();
         
-        
-        
         drop(ap);
     
-        if !config_file.is_empty() && !config_file_replace_default.is_empty() {
-            eprintln!("You can only use on of --config-file --config-file-replace-default at a time");
-            process::exit(1);
-        }
-    
-        let config_file = if !config_file.is_empty() {
-            ConfigFileType::Append(config_file)
-        } else if !config_file_replace_default.is_empty() {
-            ConfigFileType::ReplaceDefault(config_file_replace_default)
-        } else {
-            ConfigFileType::None
-        };
-    
-        (config_file, options, machine_readable)
+        (common.finish(files), match_args)
     }
 }
 
@@ -79,10 +93,60 @@ impl super::SubCommand for MatchingTypes {
     }
 
     fn run(&self, args: Vec<String>) {
-        let (config_file, options, machine_readable) = Self::setup_args(args);
-        let csv = crate::read_input_from_stdin();
-        let type_list = crate::config::get_config(config_file);
-        Self::matching_types(&csv, type_list, options, machine_readable);
+        let (mut common, mut match_args) = Self::setup_args(args);
+        let mut config = crate::config::get_config(common.config_file.clone());
+        if match_args.date_formats {
+            for type_def in types::date_formats() {
+                config.types.add_type(type_def);
+            }
+        }
+        if match_args.number_locales {
+            for type_def in types::number_locales() {
+                config.types.add_type(type_def);
+            }
+        }
+        if match_args.sized_types {
+            for type_def in types::width_types() {
+                config.types.add_type(type_def);
+            }
+        }
+        common.options.null_tokens.append(&mut config.null_tokens);
+        if let Some(threshold) = match_args.threshold {
+            if !(threshold > 0.0 && threshold <= 100.0) {
+                eprintln!("--threshold must be a percentage between 0 and 100");
+                process::exit(1);
+            }
+            common.options.threshold = Some(threshold / 100.0);
+        }
+        match_args.stats_format = match &match_args.stats[..] {
+            "" => None,
+            "human" => Some(print_result::StatsFormat::Human),
+            "json" => Some(print_result::StatsFormat::Json),
+            "csv" => Some(print_result::StatsFormat::Csv),
+            stats => {
+                eprintln!("Unknown value \"{}\" for --stats, use human, json or csv", stats);
+                process::exit(1);
+            }
+        };
+        common.options.stats = match_args.stats_format.is_some();
+        // A file which can not be checked does not stop the others.
+        let mut failed = false;
+        for file in &common.files {
+            if common.files.len() > 1 {
+                print_result::section(file, common.machine_readable);
+            }
+            let checked = match crate::input::csv_input(file, &common) {
+                Ok((csv, options)) => Self::matching_types(csv, config.types.clone(), options, common.machine_readable, &match_args),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    false
+                }
+            };
+            failed |= !checked;
+        }
+        if failed {
+            process::exit(1);
+        }
     }
 
 }
\ No newline at end of file
@@ -1,217 +1,407 @@
+use super::cache::ValueCache;
+use super::pool;
 use super::types;
-use super::vec;
-use super::Error;
-use std::thread;
+use super::{Error, FailedRow, Mismatch, Options};
 
-pub fn assert_matching_rows(
-    csv: Vec<Vec<String>>,
+#[derive(Default)]
+struct AssertState {
+    failed_assertions: Vec<FailedRow>,
+    column_count: usize,
+    /// Whether a value of a column matched, only with `Options::cache_size`.
+    caches: Vec<ValueCache<bool>>,
+}
+
+/// Checks every record against the expected types.
+///
+/// The records are checked in chunks on up to `max_threads` workers. A record
+/// is always checked as a whole by one worker, so every row is found at most
+/// once and its mismatches are in column order. The rows of all workers are
+/// sorted afterwards, which makes the result independent of the number of
+/// threads. The values are checked with a `types::TypeSet` of the expected
+/// types, like in `matching_types`. `Mismatch::header` is not set. With
+/// `Options::cache_size` values which were seen before in a column are not
+/// checked again.
+pub fn assert_matching_rows<I>(
+    records: I,
     expected_types: &[types::Type],
     max_threads: usize,
-) -> Result<Vec<(usize, Vec<usize>)>, Error> {
-    let flipped_csv = vec::flip_vec(&csv);
+    options: &Options,
+) -> Result<Vec<FailedRow>, Error>
+where
+    I: Iterator<Item = csv::ByteRecord>,
+{
+    let set = types::TypeSet::new(expected_types, options.raw_bytes);
+    let states = pool::process_chunks(
+        records,
+        max_threads,
+        || AssertState {
+            caches: options.cache_size.map_or_else(Vec::new, |size| {
+                vec![ValueCache::new(size); expected_types.len()]
+            }),
+            ..AssertState::default()
+        },
+        |state, chunk| {
+            for (row_index, record) in chunk.rows() {
+                state.column_count = state.column_count.max(record.len());
+                if record.len() > expected_types.len() {
+                    continue;
+                }
+
+                let mismatches = check_for_type_match(
+                    row_index,
+                    record,
+                    expected_types,
+                    &set,
+                    &mut state.caches,
+                    options,
+                );
+                if !mismatches.is_empty() {
+                    state
+                        .failed_assertions
+                        .push(FailedRow::new(row_index, record, mismatches));
+                }
+            }
+        },
+    )?;
 
-    if flipped_csv.len() != expected_types.len() {
-        return Err(Error::ColumnCountNotMatching);
+    let column_count = states.iter().map(|s| s.column_count).max().unwrap_or(0);
+    if column_count != expected_types.len() {
+        return Err(Error::ColumnCountNotMatching {
+            expected: expected_types.len(),
+            found: column_count,
+        });
     }
 
-    let col_sets = vec::split_vec_equal(&flipped_csv, max_threads);
-    let expected_types = vec::split_vec_equal(expected_types, max_threads);
+    let mut failed_assertions = states
+        .into_iter()
+        .flat_map(|s| s.failed_assertions)
+        .collect::<Vec<_>>();
+    failed_assertions.sort_by_key(|failed_row| failed_row.row);
 
-    check_for_type_match(col_sets, &expected_types)
+    Ok(failed_assertions)
 }
 
+/// `caches` has one cache per column or is empty.
 fn check_for_type_match(
-    col_sets: Vec<Vec<Vec<String>>>,
-    expected_types: &[Vec<types::Type>],
-) -> Result<Vec<(usize, Vec<usize>)>, Error> {
-    let mut join_handlers = Vec::new();
-    assert!(col_sets.len() == expected_types.len());
-
-    for (col_set_index, col_set) in col_sets.iter().enumerate() {
-        let col_set = col_set.clone();
-        let expected_types = expected_types[col_set_index].clone();
-
-        join_handlers.push(thread::spawn(move || {
-            let mut mismatched_rows = Vec::new();
-            for (col_index, col) in col_set.iter().enumerate() {
-                for (row_index, value) in col.iter().enumerate() {
-                    let type_def = &expected_types[col_index];
-
-                    if !types::check_if_type_matches(value, type_def) {
-                        let col = if col_set_index == 0 {
-                            col_index
-                        } else {
-                            col_index * col_set_index + col_set_index
-                        };
-                        mismatched_rows.push((row_index, col));
-                    }
-                }
+    row: usize,
+    record: &csv::ByteRecord,
+    expected_types: &[types::Type],
+    set: &types::TypeSet,
+    caches: &mut [ValueCache<bool>],
+    options: &Options,
+) -> Vec<Mismatch> {
+    record
+        .iter()
+        .zip(expected_types)
+        .enumerate()
+        .filter(|(column, (value, type_def))| {
+            let null = type_def.nullable && types::is_null(value, &options.null_tokens);
+            if null {
+                return false;
             }
-            mismatched_rows
-        }));
-    }
-    let mut mismatched_rows = Vec::new();
-    for handler in join_handlers {
-        let col_type_cols = match handler.join() {
-            Ok(ctc) => ctc,
-            Err(_) => return Err(Error::Join),
-        };
-        for col_type_col in col_type_cols {
-            mismatched_rows.push(col_type_col);
-        }
-    }
-
-    let mut failed_assertions_joined = Vec::new();
-    let mut failed_assertion_index = 0;
-    let mut failed_assertion;
-
-    while failed_assertion_index < mismatched_rows.len() {
-        failed_assertion = match mismatched_rows.get(failed_assertion_index) {
-            Some(a) => a,
-            None => continue,
-        };
-        let row = failed_assertion.0;
-        let mut failed_assertion_list = (row, Vec::new());
-        while row == failed_assertion.0 {
-            failed_assertion_list.1.push(failed_assertion.1);
-
-            failed_assertion_index += 1;
-            failed_assertion = match mismatched_rows.get(failed_assertion_index) {
-                Some(a) => a,
-                None => break,
+            let cache = caches.get_mut(*column);
+            if let Some(&matched) = cache.as_ref().and_then(|cache| cache.get(value)) {
+                return !matched;
             }
-        }
-        failed_assertions_joined.push(failed_assertion_list);
-    }
-
-    Ok(failed_assertions_joined)
+            let matched = set.check(*column, expected_types, value);
+            if let Some(cache) = cache {
+                cache.insert(value, matched);
+            }
+            !matched
+        })
+        .map(|(column, (value, type_def))| Mismatch {
+            row,
+            column,
+            header: None,
+            value: String::from_utf8_lossy(value).into_owned(),
+            expected: type_def.to_string(),
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use regex::Regex;
-
     use super::*;
 
+    fn rows(rows: Vec<Vec<&str>>) -> Vec<csv::ByteRecord> {
+        rows.into_iter().map(csv::ByteRecord::from).collect()
+    }
+
+    fn set(types: &[types::Type]) -> types::TypeSet {
+        types::TypeSet::new(types, false)
+    }
+
+    fn failed_columns(
+        result: Result<Vec<FailedRow>, Error>,
+    ) -> Result<Vec<(usize, Vec<usize>)>, Error> {
+        result.map(|rows| {
+            rows.into_iter()
+                .map(|failed_row| {
+                    let columns = failed_row.mismatches.iter().map(|m| m.column).collect();
+                    (failed_row.row, columns)
+                })
+                .collect()
+        })
+    }
+
     #[test]
     fn assert_matching_rows_column_count_not_matching() {
-        let csv = vec![vec![], vec![]];
-        let expected_types = vec![types::Type {
-            name: String::from(""),
-            pattern: Regex::new("").unwrap(),
-        }];
-        assert!(
-            if let Err(err) = assert_matching_rows(csv, &expected_types, 1) {
-                if let Error::ColumnCountNotMatching = err {
-                    true
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
+        let csv = rows(vec![vec![], vec![]]);
+        let expected_types = vec![types::Type::new("", "")];
+        assert_eq!(
+            Err(Error::ColumnCountNotMatching {
+                expected: 1,
+                found: 0
+            }),
+            assert_matching_rows(csv.into_iter(), &expected_types, 1, &Options::default())
+        );
+    }
+
+    #[test]
+    fn assert_matching_rows_too_many_columns() {
+        let csv = rows(vec![vec!["w", "w"]]);
+        let expected_types = vec![types::Type::new("str", ".*")];
+        assert_eq!(
+            Err(Error::ColumnCountNotMatching {
+                expected: 1,
+                found: 2
+            }),
+            assert_matching_rows(csv.into_iter(), &expected_types, 1, &Options::default())
         );
     }
 
     #[test]
     fn assert_matching_rows_all_matching() {
-        let csv = vec![
-            vec![String::from("w"), String::from("w"), String::from("1")],
-            vec![String::from("w"), String::from("w"), String::from("2")],
-        ];
+        let csv = rows(vec![vec!["w", "w", "1"], vec!["w", "w", "2"]]);
         let expected_types = vec![
             types::Type::new("str", "^.*$"),
             types::Type::new("str", "^.*$"),
             types::Type::new("str", r"^\d$"),
         ];
 
-        if let Ok(val) = assert_matching_rows(csv, &expected_types, 1) {
-            assert_eq!(0, val.len());
-        } else {
-            assert!(false);
-        }
+        assert_eq!(
+            Ok(vec![]),
+            assert_matching_rows(csv.into_iter(), &expected_types, 1, &Options::default())
+        );
     }
 
     #[test]
     fn check_for_type_match_all_lines_match() {
-        let col_sets = vec![
-            vec![vec![String::from("w")]],
-            vec![vec![String::from("w")]],
-            vec![vec![String::from("w")]],
-        ];
+        let record = csv::ByteRecord::from(vec!["w", "w", "w"]);
 
         let expected_types = vec![
-            vec![types::Type::new("", ".*")],
-            vec![types::Type::new("", ".*")],
-            vec![types::Type::new("", ".*")],
+            types::Type::new("", ".*"),
+            types::Type::new("", ".*"),
+            types::Type::new("", ".*"),
         ];
 
-        match check_for_type_match(col_sets, &expected_types) {
-            Ok(e) => assert_eq!(0, e.len()),
-            _ => assert!(false),
-        };
+        assert!(check_for_type_match(
+            0,
+            &record,
+            &expected_types,
+            &set(&expected_types),
+            &mut [],
+            &Options::default()
+        )
+        .is_empty());
     }
 
     #[test]
     fn check_for_type_match_not_first_line() {
-        let col_sets = vec![
-            vec![vec![String::from("w")]],
-            vec![vec![String::from("w")]],
-            vec![vec![String::from("w")]],
-        ];
+        let csv = rows(vec![vec!["w", "w", "w"]]);
 
         let expected_types = vec![
-            vec![types::Type::new("", r"\d")],
-            vec![types::Type::new("", ".*")],
-            vec![types::Type::new("", ".*")],
+            types::Type::new("", r"\d"),
+            types::Type::new("", ".*"),
+            types::Type::new("", ".*"),
         ];
 
-        match check_for_type_match(col_sets, &expected_types) {
-            Ok(e) => assert_eq!(&[(0, vec!(0))], &e[..]),
-            _ => assert!(false),
-        };
+        assert_eq!(
+            Ok(vec![(0, vec![0])]),
+            failed_columns(assert_matching_rows(
+                csv.into_iter(),
+                &expected_types,
+                1,
+                &Options::default()
+            ))
+        );
     }
 
     #[test]
     fn check_for_type_match_not_first_second_row() {
-        let col_sets = vec![
-            vec![vec![String::from("2"), String::from("w")]],
-            vec![vec![String::from("w"), String::from("w")]],
-            vec![vec![String::from("w"), String::from("w")]],
-        ];
+        let csv = rows(vec![vec!["2", "w", "w"], vec!["w", "w", "w"]]);
 
         let expected_types = vec![
-            vec![types::Type::new("", r"\d")],
-            vec![types::Type::new("", ".*")],
-            vec![types::Type::new("", ".*")],
+            types::Type::new("", r"\d"),
+            types::Type::new("", ".*"),
+            types::Type::new("", ".*"),
         ];
 
-        match check_for_type_match(col_sets, &expected_types) {
-            Ok(e) => assert_eq!(&[(1, vec!(0))], &e[..]),
-            _ => assert!(false),
-        };
+        assert_eq!(
+            Ok(vec![(1, vec![0])]),
+            failed_columns(assert_matching_rows(
+                csv.into_iter(),
+                &expected_types,
+                1,
+                &Options::default()
+            ))
+        );
     }
 
     #[test]
     fn test() {
-        let col_sets = vec![
-            vec![vec![String::from("w"), String::from("w")]],
-            vec![vec![String::from("2"), String::from("w")]],
-            vec![vec![String::from("w"), String::from("2")]],
-        ];
+        let csv = rows(vec![vec!["w", "2", "w"], vec!["w", "w", "2"]]);
 
         let expected_types = vec![
-            vec![types::Type::new("string", ".*")],
-            vec![types::Type::new("int", r"\d*")],
-            vec![types::Type::new("int", r"\d*")],
+            types::Type::new("string", ".*"),
+            types::Type::new("int", r"\d*"),
+            types::Type::new("int", r"\d*"),
         ];
 
-        match check_for_type_match(col_sets, &expected_types) {
+        match failed_columns(assert_matching_rows(
+            csv.into_iter(),
+            &expected_types,
+            1,
+            &Options::default(),
+        )) {
             Ok(e) => {
                 assert!(e.contains(&(0, vec!(2))));
                 assert!(e.contains(&(1, vec!(1))));
             }
-            _ => assert!(false),
+            Err(e) => panic!("{}", e),
         };
     }
+
+    #[test]
+    fn assert_matching_rows_ordered_with_multiple_threads() {
+        let csv = (0..pool::CHUNK_SIZE * 4)
+            .map(|i| {
+                let value = if i % 3 == 0 {
+                    "w".to_owned()
+                } else {
+                    i.to_string()
+                };
+                csv::ByteRecord::from(vec![value])
+            })
+            .collect::<Vec<_>>();
+        let expected_types = vec![types::Type::new("int", r"\d+")];
+
+        let expected = (0..pool::CHUNK_SIZE * 4)
+            .filter(|i| i % 3 == 0)
+            .map(|i| (i, vec![0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            Ok(expected),
+            failed_columns(assert_matching_rows(
+                csv.into_iter(),
+                &expected_types,
+                3,
+                &Options::default()
+            ))
+        );
+    }
+
+    #[test]
+    fn check_for_type_match_reports_values() {
+        let record = csv::ByteRecord::from(vec!["1", "x", "y"]);
+        let expected_types = vec![
+            types::Type::new("int", r"\d+"),
+            types::Type::new("int", r"\d+"),
+            types::Type::new("str", ".*"),
+        ];
+        assert_eq!(
+            vec![Mismatch {
+                row: 7,
+                column: 1,
+                header: None,
+                value: String::from("x"),
+                expected: String::from("int"),
+            }],
+            check_for_type_match(
+                7,
+                &record,
+                &expected_types,
+                &set(&expected_types),
+                &mut [],
+                &Options::default()
+            )
+        );
+    }
+
+    #[test]
+    fn assert_matching_rows_same_result_for_any_thread_count() {
+        let columns = 7;
+        let csv = (0..pool::CHUNK_SIZE * 3 + 17)
+            .map(|row| {
+                let values = (0..columns)
+                    .map(|col| {
+                        if (row + col) % 5 == 0 || row % 11 == col {
+                            "x".to_owned()
+                        } else {
+                            row.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                csv::ByteRecord::from(values)
+            })
+            .collect::<Vec<_>>();
+        let expected_types = vec![types::Type::new("int", r"\d+"); columns];
+
+        let expected = (0..csv.len())
+            .map(|row| {
+                let failed = (0..columns)
+                    .filter(|col| (row + col) % 5 == 0 || row % 11 == *col)
+                    .collect::<Vec<_>>();
+                (row, failed)
+            })
+            .filter(|(_, failed)| !failed.is_empty())
+            .collect::<Vec<_>>();
+        let cached = Options {
+            cache_size: Some(100),
+            ..Options::default()
+        };
+        for threads in 1..=8 {
+            for options in [&Options::default(), &cached] {
+                assert_eq!(
+                    Ok(expected.clone()),
+                    failed_columns(assert_matching_rows(
+                        csv.clone().into_iter(),
+                        &expected_types,
+                        threads,
+                        options
+                    ))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn check_for_type_match_nullable() {
+        let record = csv::ByteRecord::from(vec!["", "NA", ""]);
+        let int = types::Type::new("int", r"\d+");
+        let expected_types = vec![
+            int.clone().with_nullable(true),
+            int.clone().with_nullable(true),
+            int,
+        ];
+        let options = Options {
+            null_tokens: vec![String::new(), String::from("NA")],
+            ..Options::default()
+        };
+        let mismatches = check_for_type_match(
+            0,
+            &record,
+            &expected_types,
+            &set(&expected_types),
+            &mut [],
+            &options,
+        );
+        assert_eq!(
+            vec![(2, String::from("int"))],
+            mismatches
+                .into_iter()
+                .map(|m| (m.column, m.expected))
+                .collect::<Vec<_>>()
+        );
+    }
 }
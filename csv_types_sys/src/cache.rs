@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+/// Results of the distinct values of a column, see `Options::cache_size`.
+///
+/// Once `limit` values are cached new values are checked every time. The
+/// number of distinct values is only known as long as no value was left out.
+#[derive(Clone, Debug)]
+pub struct ValueCache<T> {
+    values: HashMap<Vec<u8>, T>,
+    limit: usize,
+    overflow: bool,
+}
+
+impl<T> ValueCache<T> {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            values: HashMap::new(),
+            limit,
+            overflow: false,
+        }
+    }
+
+    pub fn get(&self, value: &[u8]) -> Option<&T> {
+        self.values.get(value)
+    }
+
+    pub fn insert(&mut self, value: &[u8], result: T) {
+        if self.values.len() < self.limit {
+            self.values.insert(value.to_vec(), result);
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    /// Adds the values of another worker's cache.
+    pub fn merge(&mut self, other: Self) {
+        self.overflow |= other.overflow;
+        for (value, result) in other.values {
+            if !self.values.contains_key(&value) {
+                self.insert(&value, result);
+            }
+        }
+    }
+
+    /// Number of distinct values if all of them are cached.
+    pub fn distinct(&self) -> Option<u64> {
+        if self.overflow {
+            None
+        } else {
+            Some(self.values.len() as u64)
+        }
+    }
+}
+
+/// Merges the caches of all workers column by column.
+pub fn merge<T>(results: Vec<Vec<ValueCache<T>>>) -> Vec<ValueCache<T>> {
+    let mut results = results.into_iter();
+    let mut merged = results.next().unwrap_or_default();
+    for caches in results {
+        for (col_index, cache) in caches.into_iter().enumerate() {
+            match merged.get_mut(col_index) {
+                Some(merged_cache) => merged_cache.merge(cache),
+                None => merged.push(cache),
+            }
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_distinct_values_up_to_limit() {
+        let mut cache = ValueCache::new(2);
+        cache.insert(b"a", true);
+        cache.insert(b"b", false);
+        assert_eq!(Some(&false), cache.get(b"b"));
+        assert_eq!(Some(2), cache.distinct());
+
+        let mut other = ValueCache::new(2);
+        other.insert(b"a", true);
+        cache.merge(other.clone());
+        assert_eq!(Some(2), cache.distinct());
+        other.insert(b"c", true);
+        cache.merge(other);
+        assert_eq!(None, cache.get(b"c"));
+        assert_eq!(None, cache.distinct());
+    }
+}
@@ -0,0 +1,409 @@
+use super::CsvInput;
+use std::io::{self, Read};
+
+/// Number of bytes which are looked at to guess the dialect.
+pub const SAMPLE_SIZE: usize = 16 * 1024;
+
+const DELIMITERS: [u8; 5] = [b',', b';', b'\t', b'|', b':'];
+const QUOTES: [u8; 2] = [b'"', b'\''];
+
+/// Describes how the fields and records of a CSV file are separated.
+#[derive(Clone, Copy, Debug)]
+pub struct Dialect {
+    /// Byte between two fields.
+    pub delimiter: u8,
+    /// Byte used to quote fields.
+    pub quote: u8,
+    /// Byte used to escape quotes inside of quoted fields.
+    pub escape: Option<u8>,
+    /// Two quotes inside of a quoted field are read as one quote.
+    pub double_quote: bool,
+    /// Lines starting with this byte are ignored.
+    pub comment: Option<u8>,
+    /// End of a record. `CRLF` accepts `\r`, `\n` and `\r\n`.
+    pub terminator: csv::Terminator,
+}
+
+impl Dialect {
+    pub(crate) fn reader_builder(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .has_headers(false)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .double_quote(self.double_quote)
+            .comment(self.comment)
+            .terminator(self.terminator);
+        builder
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            double_quote: true,
+            comment: None,
+            terminator: csv::Terminator::CRLF,
+        }
+    }
+}
+
+/// Line ending which is used most often in a sample.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    CrLf,
+    Lf,
+    Cr,
+}
+
+impl LineEnding {
+    /// `CRLF` already reads `\r\n` and `\n`. Only with `\r` alone a `\n` is
+    /// part of a field instead of ending the record.
+    pub fn terminator(self) -> csv::Terminator {
+        match self {
+            Self::CrLf | Self::Lf => csv::Terminator::CRLF,
+            Self::Cr => csv::Terminator::Any(b'\r'),
+        }
+    }
+}
+
+/// Result of guessing the dialect of CSV data.
+#[derive(Clone, Copy, Debug)]
+pub struct Sniffed {
+    pub dialect: Dialect,
+    /// The first record looks like a header.
+    pub has_headers: bool,
+    pub line_ending: LineEnding,
+}
+
+/// Guesses the dialect of the CSV data in `sample` and whether it starts with a header.
+/// The terminator of the dialect is the one of the guessed line ending.
+pub fn sniff(sample: &[u8]) -> Sniffed {
+    let sample = complete_lines(sample);
+    let quote = guess_quote(sample);
+    let escape = if sample.windows(2).any(|w| w == [b'\\', quote]) {
+        Some(b'\\')
+    } else {
+        None
+    };
+
+    let mut dialect = Dialect {
+        quote,
+        escape,
+        ..Dialect::default()
+    };
+    let mut best_score = None;
+    for &delimiter in DELIMITERS.iter() {
+        let candidate = Dialect {
+            delimiter,
+            ..dialect
+        };
+        let score = delimiter_score(&read_sample(&candidate, sample), delimiter);
+        if score > best_score {
+            best_score = score;
+            dialect = candidate;
+        }
+    }
+
+    let line_ending = guess_line_ending(sample);
+    let dialect = Dialect {
+        terminator: line_ending.terminator(),
+        ..dialect
+    };
+    let records = read_sample(&dialect, sample);
+    Sniffed {
+        dialect,
+        has_headers: guess_has_headers(&records),
+        line_ending,
+    }
+}
+
+/// Guesses the dialect from the start of the input.
+///
+/// The bytes which are read for guessing are put in front of the remaining
+/// input again so nothing is lost. Readers which are already configured can
+/// not be sniffed.
+pub fn sniff_input(input: CsvInput<'_>) -> io::Result<(Sniffed, CsvInput<'_>)> {
+    match input {
+        CsvInput::Csv(csv) => {
+            let sample = &csv.as_bytes()[..csv.len().min(SAMPLE_SIZE)];
+            Ok((sniff(sample), CsvInput::Csv(csv)))
+        }
+        CsvInput::Read(mut read) => {
+            let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+            read.by_ref()
+                .take(SAMPLE_SIZE as u64)
+                .read_to_end(&mut sample)?;
+            let sniffed = sniff(&sample);
+            Ok((
+                sniffed,
+                CsvInput::Read(Box::new(io::Cursor::new(sample).chain(read))),
+            ))
+        }
+        CsvInput::Reader(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a configured reader can not be sniffed",
+        )),
+    }
+}
+
+/// Cuts off the last line if it could be incomplete.
+fn complete_lines(sample: &[u8]) -> &[u8] {
+    if sample.len() < SAMPLE_SIZE {
+        return sample;
+    }
+    match sample.iter().rposition(|&b| b == b'\n' || b == b'\r') {
+        Some(end) => &sample[..end],
+        None => sample,
+    }
+}
+
+/// Picks the quote character which starts or ends the most fields.
+fn guess_quote(sample: &[u8]) -> u8 {
+    let is_boundary = |b: Option<&u8>| match b {
+        None => true,
+        Some(b) => DELIMITERS.contains(b) || *b == b'\n' || *b == b'\r',
+    };
+    let mut quote = Dialect::default().quote;
+    let mut best_count = 0;
+    for &candidate in QUOTES.iter() {
+        let count = (0..sample.len())
+            .filter(|&i| sample[i] == candidate)
+            .filter(|&i| {
+                is_boundary(i.checked_sub(1).and_then(|i| sample.get(i)))
+                    || is_boundary(sample.get(i + 1))
+            })
+            .count();
+        if count > best_count {
+            best_count = count;
+            quote = candidate;
+        }
+    }
+    quote
+}
+
+fn read_sample(dialect: &Dialect, sample: &[u8]) -> Vec<csv::StringRecord> {
+    dialect
+        .reader_builder()
+        .flexible(true)
+        .from_reader(sample)
+        .records()
+        .map_while(Result::ok)
+        .collect()
+}
+
+/// Rates how well `delimiter` splits the records.
+///
+/// Records should have the same number of fields, more than one of them, and
+/// the fields should not contain other delimiters unless they are numbers
+/// with a decimal comma.
+fn delimiter_score(records: &[csv::StringRecord], delimiter: u8) -> Option<(f64, f64)> {
+    let mut widths = records.iter().map(|r| r.len()).collect::<Vec<_>>();
+    widths.sort_unstable();
+    let (width, width_count) = widths
+        .chunk_by(|a, b| a == b)
+        .map(|chunk| (chunk[0], chunk.len()))
+        .max_by_key(|&(width, count)| (count, width))?;
+    if width < 2 {
+        return None;
+    }
+
+    let fields = records.iter().flat_map(|r| r.iter()).collect::<Vec<_>>();
+    let clean_fields = fields
+        .iter()
+        .filter(|field| {
+            is_number(field)
+                || !field
+                    .bytes()
+                    .any(|b| b != delimiter && DELIMITERS.contains(&b))
+        })
+        .count();
+
+    Some((
+        width_count as f64 / records.len() as f64,
+        clean_fields as f64 / fields.len() as f64,
+    ))
+}
+
+fn is_number(value: &str) -> bool {
+    let value = value.trim();
+    value.bytes().any(|b| b.is_ascii_digit())
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || b"+-.,' ".contains(&b))
+}
+
+/// Compares the first record with the rest like a spreadsheet user would.
+///
+/// Every column where all other values are numbers or have the same length
+/// votes for a header if the first value breaks that pattern and against one
+/// if it does not.
+fn guess_has_headers(records: &[csv::StringRecord]) -> bool {
+    let (header, rows) = match records.split_first() {
+        Some((header, rows)) if !rows.is_empty() => (header, rows),
+        _ => return false,
+    };
+
+    let mut votes = 0;
+    for (col_index, header_value) in header.iter().enumerate() {
+        let values = rows
+            .iter()
+            .filter_map(|row| row.get(col_index))
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            continue;
+        }
+
+        if values.iter().all(|v| is_number(v)) {
+            votes += if is_number(header_value) { -1 } else { 1 };
+        } else if values.iter().all(|v| v.len() == values[0].len()) {
+            votes += if header_value.len() == values[0].len() {
+                -1
+            } else {
+                1
+            };
+        }
+    }
+    votes > 0
+}
+
+fn guess_line_ending(sample: &[u8]) -> LineEnding {
+    let crlf = sample.windows(2).filter(|w| w == b"\r\n").count();
+    let cr = sample.iter().filter(|&&b| b == b'\r').count() - crlf;
+    let lf = sample.iter().filter(|&&b| b == b'\n').count() - crlf;
+    if crlf >= lf && crlf >= cr && crlf > 0 {
+        LineEnding::CrLf
+    } else if cr > lf {
+        LineEnding::Cr
+    } else {
+        LineEnding::Lf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(dialect: Dialect, csv: &str) -> Vec<Vec<String>> {
+        dialect
+            .reader_builder()
+            .from_reader(csv.as_bytes())
+            .records()
+            .map(|record| record.unwrap().iter().map(str::to_owned).collect())
+            .collect()
+    }
+
+    #[test]
+    fn default_dialect() {
+        assert_eq!(
+            vec![vec!["a".to_owned(), "b,c".to_owned()]],
+            read(Dialect::default(), "a,\"b,c\"")
+        );
+    }
+
+    #[test]
+    fn custom_dialect() {
+        let dialect = Dialect {
+            delimiter: b';',
+            quote: b'\'',
+            escape: Some(b'\\'),
+            double_quote: false,
+            comment: Some(b'#'),
+            terminator: csv::Terminator::Any(b'|'),
+        };
+        assert_eq!(
+            vec![
+                vec!["a".to_owned(), "b;'c".to_owned()],
+                vec!["d".to_owned(), "e".to_owned()]
+            ],
+            read(dialect, "a;'b;\\'c'|d;e")
+        );
+    }
+
+    #[test]
+    fn sniff_comma() {
+        let sniffed = sniff(b"name,speed,direction\nBremen,12.5,270\nOldenburg,3.1,90\n");
+        assert_eq!(b',', sniffed.dialect.delimiter);
+        assert_eq!(b'"', sniffed.dialect.quote);
+        assert!(sniffed.has_headers);
+        assert_eq!(LineEnding::Lf, sniffed.line_ending);
+    }
+
+    #[test]
+    fn sniff_carriage_return() {
+        let csv = "name,note\rBremen,\"a\"\rOldenburg,b\nc\r";
+        let sniffed = sniff(csv.as_bytes());
+        assert_eq!(LineEnding::Cr, sniffed.line_ending);
+        assert_eq!(
+            vec![
+                vec!["name".to_owned(), "note".to_owned()],
+                vec!["Bremen".to_owned(), "a".to_owned()],
+                vec!["Oldenburg".to_owned(), "b\nc".to_owned()]
+            ],
+            read(sniffed.dialect, csv)
+        );
+    }
+
+    #[test]
+    fn sniff_semicolon_with_decimal_comma() {
+        let sniffed = sniff(b"1,5;2,5;a\r\n3,25;4;b\r\n7;8,75;c\r\n");
+        assert_eq!(b';', sniffed.dialect.delimiter);
+        assert!(!sniffed.has_headers);
+        assert_eq!(LineEnding::CrLf, sniffed.line_ending);
+    }
+
+    #[test]
+    fn sniff_tab_and_single_quotes() {
+        let sniffed = sniff(b"'a\tb'\t1\n'c'\t2\n'd'\t3\n");
+        assert_eq!(b'\t', sniffed.dialect.delimiter);
+        assert_eq!(b'\'', sniffed.dialect.quote);
+        assert_eq!(None, sniffed.dialect.escape);
+    }
+
+    #[test]
+    fn sniff_backslash_escape() {
+        let sniffed = sniff(b"\"a \\\"b\\\"\",1\n\"c\",2\n");
+        assert_eq!(b',', sniffed.dialect.delimiter);
+        assert_eq!(Some(b'\\'), sniffed.dialect.escape);
+    }
+
+    #[test]
+    fn sniff_input_keeps_all_data() {
+        let data = "a;b\n1;2\n";
+        let (sniffed, input) = sniff_input(CsvInput::Read(Box::new(data.as_bytes()))).unwrap();
+        assert_eq!(b';', sniffed.dialect.delimiter);
+        let mut read = String::new();
+        match input {
+            CsvInput::Read(mut r) => r.read_to_string(&mut read).unwrap(),
+            _ => panic!("expected a reader"),
+        };
+        assert_eq!(data, read);
+    }
+
+    #[test]
+    fn headers_without_numbers() {
+        let records = vec![
+            csv::StringRecord::from(vec!["code", "flag"]),
+            csv::StringRecord::from(vec!["AB", "y"]),
+            csv::StringRecord::from(vec!["CD", "z"]),
+        ];
+        assert!(guess_has_headers(&records));
+        assert!(!guess_has_headers(&records[1..]));
+    }
+
+    #[test]
+    fn comment_lines_are_ignored() {
+        let dialect = Dialect {
+            comment: Some(b'#'),
+            ..Dialect::default()
+        };
+        assert_eq!(
+            vec![vec!["a".to_owned(), "b".to_owned()]],
+            read(dialect, "#x,y,z\na,b")
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::io::{self, Read};
+
+/// Number of bytes which are looked at to guess the encoding.
+pub const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Character encoding of the input.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Encoding {
+    /// The input is read as UTF-8 without decoding it.
+    #[default]
+    Utf8,
+    /// Guess the encoding from a byte order mark or the start of the input.
+    Auto,
+    /// Decode the input from this encoding.
+    Other(&'static encoding_rs::Encoding),
+}
+
+impl Encoding {
+    /// Looks up an encoding by a label like `windows-1252`, `latin1` or `auto`.
+    pub fn for_label(label: &str) -> Option<Self> {
+        if label.eq_ignore_ascii_case("auto") {
+            return Some(Self::Auto);
+        }
+        match encoding_rs::Encoding::for_label(label.as_bytes())? {
+            encoding if encoding == encoding_rs::UTF_8 => Some(Self::Utf8),
+            encoding => Some(Self::Other(encoding)),
+        }
+    }
+}
+
+/// Decodes the input to UTF-8.
+///
+/// Returns the decoded input and the name of the encoding which is used.
+/// A byte order mark always takes precedence over the given encoding and is
+/// removed. UTF-8 is passed through unchanged so invalid bytes are still
+/// reported instead of being replaced.
+pub fn decode<'a>(
+    input: Box<dyn Read + 'a>,
+    encoding: Encoding,
+) -> io::Result<(Box<dyn Read + 'a>, &'static str)> {
+    let (input, bom) = skip_bom(input)?;
+    let (input, encoding) = match (bom, encoding) {
+        (Some(encoding), _) => (input, encoding),
+        (None, Encoding::Utf8) => (input, encoding_rs::UTF_8),
+        (None, Encoding::Other(encoding)) => (input, encoding),
+        (None, Encoding::Auto) => match guess(input)? {
+            (input, None) => (input, encoding_rs::UTF_8),
+            (input, Some(encoding)) => (input, encoding),
+        },
+    };
+    if encoding == encoding_rs::UTF_8 {
+        return Ok((input, encoding.name()));
+    }
+
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .bom_sniffing(false)
+        .build(input);
+    Ok((Box::new(decoder), encoding.name()))
+}
+
+/// Removes a byte order mark from the start of the input and returns the
+/// encoding it stands for.
+fn skip_bom<'a>(
+    mut input: Box<dyn Read + 'a>,
+) -> io::Result<(Box<dyn Read + 'a>, Option<&'static encoding_rs::Encoding>)> {
+    let mut start = Vec::with_capacity(3);
+    input.by_ref().take(3).read_to_end(&mut start)?;
+    let (encoding, length) = match encoding_rs::Encoding::for_bom(&start) {
+        Some((encoding, length)) => (Some(encoding), length),
+        None => (None, 0),
+    };
+    start.drain(..length);
+    Ok((Box::new(io::Cursor::new(start).chain(input)), encoding))
+}
+
+/// Reads the start of the input to guess its encoding. Returns `None` for
+/// UTF-8.
+fn guess<'a>(
+    mut input: Box<dyn Read + 'a>,
+) -> io::Result<(Box<dyn Read + 'a>, Option<&'static encoding_rs::Encoding>)> {
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    input
+        .by_ref()
+        .take(SAMPLE_SIZE as u64)
+        .read_to_end(&mut sample)?;
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(&sample, sample.len() < SAMPLE_SIZE);
+    let encoding =
+        Some(detector.guess(None, true)).filter(|&encoding| encoding != encoding_rs::UTF_8);
+
+    Ok((Box::new(io::Cursor::new(sample).chain(input)), encoding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_to_string(input: &'static [u8], encoding: Encoding) -> (String, &'static str) {
+        let (mut read, name) = decode(Box::new(input), encoding).unwrap();
+        let mut decoded = String::new();
+        read.read_to_string(&mut decoded).unwrap();
+        (decoded, name)
+    }
+
+    #[test]
+    fn encoding_for_label() {
+        assert_eq!(Some(Encoding::Auto), Encoding::for_label("AUTO"));
+        assert_eq!(Some(Encoding::Utf8), Encoding::for_label("utf8"));
+        assert_eq!(
+            Some(Encoding::Other(encoding_rs::WINDOWS_1252)),
+            Encoding::for_label("iso-8859-1")
+        );
+        assert_eq!(None, Encoding::for_label("unknown"));
+    }
+
+    #[test]
+    fn decode_windows_1252() {
+        let input = b"Stra\xdfe;M\xfcnchen";
+        assert_eq!(
+            (String::from("Straße;München"), "windows-1252"),
+            decode_to_string(input, Encoding::Other(encoding_rs::WINDOWS_1252))
+        );
+    }
+
+    #[test]
+    fn decode_auto() {
+        let input = b"Stadt;Stra\xdfe\nM\xfcnchen;Hauptstra\xdfe\nK\xf6ln;Domstra\xdfe\n";
+        let (decoded, name) = decode_to_string(input, Encoding::Auto);
+        assert_eq!("windows-1252", name);
+        assert!(decoded.starts_with("Stadt;Straße\nMünchen"));
+
+        let input = "Stadt;Straße\nMünchen;Hauptstraße\n".as_bytes();
+        assert_eq!("UTF-8", decode_to_string(input, Encoding::Auto).1);
+    }
+
+    #[test]
+    fn decode_utf8_keeps_invalid_bytes() {
+        let (mut read, _) = decode(Box::new(&b"a\xff"[..]), Encoding::Utf8).unwrap();
+        let mut decoded = Vec::new();
+        read.read_to_end(&mut decoded).unwrap();
+        assert_eq!(b"a\xff", &decoded[..]);
+    }
+
+    #[test]
+    fn decode_removes_bom() {
+        for encoding in [
+            Encoding::Utf8,
+            Encoding::Auto,
+            Encoding::Other(encoding_rs::WINDOWS_1252),
+        ] {
+            assert_eq!(
+                (String::from("a,b"), "UTF-8"),
+                decode_to_string(b"\xef\xbb\xbfa,b", encoding)
+            );
+            assert_eq!(
+                (String::from("a,b"), "UTF-16LE"),
+                decode_to_string(b"\xff\xfea\x00,\x00b\x00", encoding)
+            );
+        }
+        let (mut read, _) = decode(Box::new(&b"\xef\xbb\xbfa\xff"[..]), Encoding::Auto).unwrap();
+        let mut decoded = Vec::new();
+        read.read_to_end(&mut decoded).unwrap();
+        assert_eq!(b"a\xff", &decoded[..]);
+        assert_eq!(
+            (String::from("a"), "UTF-8"),
+            decode_to_string(b"a", Encoding::Utf8)
+        );
+    }
+
+    #[test]
+    fn decode_utf16_with_bom() {
+        let input = b"\xff\xfea\x00,\x00b\x00";
+        assert_eq!(
+            (String::from("a,b"), "UTF-16LE"),
+            decode_to_string(input, Encoding::Auto)
+        );
+    }
+}
@@ -1,94 +1,461 @@
 use std::fmt::Display;
+use std::io;
+use std::thread;
 
 mod assert_matching_rows;
+mod cache;
+pub mod dialect;
+pub mod encoding;
+pub mod matcher;
 mod matching_types;
+pub mod native;
+mod pool;
+mod reader;
 pub mod types;
-mod vec;
 
 pub fn get_types(
     csv: CsvInput,
     type_list: types::TypeList,
     options: Options,
-) -> Result<(Vec<String>, Vec<Vec<types::Type>>), Error> {
+) -> Result<MatchResult, Error> {
     let has_headers = options.has_headers;
-    let max_threads = if let Some(threads) = options.max_threads {
-        if threads < 1 {
-            return Err(Error::ThreadCount);
-        }
-        threads
-    } else {
-        1
-    };
+    let max_threads = max_threads(&options)?;
+    type_list.check_parents()?;
 
-    let mut csv = vec::csv_to_vec(csv);
+    let mut reader = reader::csv_reader(csv, &options)?;
+    let mut records =
+        reader::Records::new(&mut reader, options.on_error).with_raw_bytes(options.raw_bytes);
+    if options.flexible {
+        records = records.with_width(reader::Width::First);
+    }
 
     let headers = if has_headers {
-        get_header(&mut csv)
+        get_header(&mut records)
     } else {
         Vec::new()
     };
 
-    let types = matching_types::get_matching_types(csv, type_list, max_threads)?;
+    let columns =
+        matching_types::get_matching_types(records.by_ref(), &type_list, max_threads, &options)?;
 
-    Ok((headers, types))
+    let (malformed, ragged) = records.finish()?;
+
+    Ok(MatchResult {
+        headers,
+        columns,
+        malformed,
+        ragged,
+    })
 }
 
 pub fn assert_columns_match(
     csv: CsvInput,
     expected_types: Vec<types::Type>,
     options: Options,
-) -> Result<Vec<(usize, Vec<usize>)>, Error> {
+) -> Result<AssertResult, Error> {
     let has_headers = options.has_headers;
-    let max_threads = if let Some(threads) = options.max_threads {
-        if threads < 1 {
-            return Err(Error::ThreadCount);
-        }
-        threads
+    let max_threads = max_threads(&options)?;
+
+    let mut reader = reader::csv_reader(csv, &options)?;
+    let mut records =
+        reader::Records::new(&mut reader, options.on_error).with_raw_bytes(options.raw_bytes);
+
+    // The header may have another width than the types, so it is read first.
+    let headers = if has_headers {
+        get_header(&mut records)
     } else {
-        1
+        Vec::new()
     };
+    if options.flexible {
+        records = records.with_width(reader::Width::Fields(expected_types.len()));
+    }
 
-    let mut csv = vec::csv_to_vec(csv);
+    let failed_rows = assert_matching_rows::assert_matching_rows(
+        records.by_ref(),
+        &expected_types,
+        max_threads,
+        &options,
+    );
+    let (malformed, ragged) = records.finish()?;
 
-    if has_headers {
-        get_header(&mut csv);
+    let mut failed_rows = failed_rows?;
+    for mismatch in failed_rows.iter_mut().flat_map(|row| &mut row.mismatches) {
+        mismatch.header = headers.get(mismatch.column).cloned();
     }
 
-    let failed_assertions =
-        assert_matching_rows::assert_matching_rows(csv, &expected_types, max_threads)?;
+    Ok(AssertResult {
+        failed_rows,
+        malformed,
+        ragged,
+    })
+}
 
-    Ok(failed_assertions)
+fn max_threads(options: &Options) -> Result<usize, Error> {
+    match options.max_threads {
+        Some(threads) if threads < 1 => Err(Error::ThreadCount),
+        Some(threads) => Ok(threads),
+        None => Ok(thread::available_parallelism().map_or(1, |threads| threads.get())),
+    }
 }
 
-fn get_header(csv: &mut Vec<Vec<String>>) -> Vec<String> {
-    let headers = csv[0].clone();
-    csv.remove(0);
-    headers
+fn get_header<I: Iterator<Item = csv::ByteRecord>>(records: &mut I) -> Vec<String> {
+    match records.next() {
+        Some(record) => record
+            .iter()
+            .map(|field| String::from_utf8_lossy(field).into_owned())
+            .collect(),
+        None => Vec::new(),
+    }
 }
 
 pub enum CsvInput<'a> {
+    /// CSV data which is already in memory.
     Csv(&'a str),
-    Reader(csv::Reader<&'a [u8]>),
+    /// Any source of CSV data like a file or stdin.
+    Read(Box<dyn io::Read + 'a>),
+    /// A reader which has already been configured.
+    Reader(csv::Reader<Box<dyn io::Read + 'a>>),
+}
+
+/// Result of `get_types`.
+#[derive(Debug)]
+pub struct MatchResult {
+    /// Values of the first record if `Options::has_headers` is set.
+    pub headers: Vec<String>,
+    /// Types and counts of every column.
+    pub columns: Vec<Column>,
+    /// Records which could not be parsed and have been skipped.
+    pub malformed: Vec<MalformedRecord>,
+    /// Records which have been skipped in flexible mode because of their width.
+    pub ragged: Vec<RaggedRecord>,
+}
+
+/// A column of the result of `get_types`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    /// Types which matched every value of the column which is not null, from
+    /// the most to the least specific. They are nullable if the column
+    /// contains nulls.
+    pub types: Vec<types::Type>,
+    /// Number of values which are not null and matched each of `types`, in
+    /// the same order.
+    pub matched: Vec<u64>,
+    /// Number of values in the column, nulls included.
+    pub values: u64,
+    /// Number of values which are one of `Options::null_tokens`.
+    pub nulls: u64,
+    /// Values which did not match all of `types`, ordered by row. Only used
+    /// with `Options::threshold` and limited to `MAX_OUTLIERS`.
+    pub outliers: Vec<Outlier>,
+    /// Statistics of the column, only collected with `Options::stats`.
+    pub stats: Option<ColumnStats>,
+    /// Number of distinct values which are not null. Only known with
+    /// `Options::cache_size` if every value fit into the cache.
+    pub distinct: Option<u64>,
+}
+
+impl Column {
+    /// The most specific type which matched.
+    pub fn best_type(&self) -> Option<&types::Type> {
+        self.types.first()
+    }
+
+    /// Share of the values which are not null and matched `types[index]`,
+    /// between 0 and 1.
+    pub fn match_ratio(&self, index: usize) -> f64 {
+        self.ratio(self.matched[index])
+    }
+
+    /// Share of the values which are not null that `matched` stands for, like
+    /// the matches of `ColumnStats::types`. Without such values it is 1.
+    pub fn ratio(&self, matched: u64) -> f64 {
+        let checked = self.values - self.nulls;
+        if checked == 0 {
+            1.0
+        } else {
+            matched as f64 / checked as f64
+        }
+    }
+
+    /// Share of the values which are null, between 0 and 1.
+    pub fn null_ratio(&self) -> f64 {
+        if self.values == 0 {
+            0.0
+        } else {
+            self.nulls as f64 / self.values as f64
+        }
+    }
+}
+
+/// Number of outliers which are kept per column.
+pub const MAX_OUTLIERS: usize = 100;
+
+/// Number of distinct values which are counted per column.
+pub const MAX_DISTINCT: usize = 100_000;
+
+/// Statistics of a column collected with `Options::stats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnStats {
+    /// Every type of the type list in list order, also the rejected ones.
+    pub types: Vec<TypeStats>,
+    /// Number of empty values, nulls included.
+    pub empties: u64,
+    /// Number of distinct values which are not null.
+    pub distinct: u64,
+    /// More than `MAX_DISTINCT` distinct values were found, so `distinct` is
+    /// a lower bound.
+    pub distinct_overflow: bool,
+    /// Shortest and longest value which is not null, in characters. Values
+    /// which are not valid UTF-8 are counted in bytes.
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+}
+
+/// How a type did in a column, see `ColumnStats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeStats {
+    pub name: String,
+    /// Number of values which are not null and matched the type.
+    pub matched: u64,
+    /// The first value which did not match.
+    pub first_failure: Option<Outlier>,
+}
+
+/// A value which did not match a type that is reported because of
+/// `Options::threshold`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outlier {
+    /// Index of the row, see `FailedRow::row`.
+    pub row: usize,
+    /// Number of the record starting at 1, the header included.
+    pub record: u64,
+    /// Line the record starts on, starting at 1.
+    pub line: u64,
+    /// Byte offset of the record from the start of the input.
+    pub byte: u64,
+    /// Index of the column starting at 0.
+    pub column: usize,
+    /// The value. Invalid UTF-8 is replaced.
+    pub value: String,
+    /// Names of the reported types which did not match.
+    pub types: Vec<String>,
+}
+
+impl Outlier {
+    fn new(row: usize, record: &csv::ByteRecord, column: usize, value: &[u8]) -> Self {
+        let position = position(record);
+        Self {
+            row,
+            record: position.record() + 1,
+            line: position.line(),
+            byte: position.byte(),
+            column,
+            value: String::from_utf8_lossy(value).into_owned(),
+            types: Vec::new(),
+        }
+    }
+}
+
+/// Result of `assert_columns_match`.
+#[derive(Debug, PartialEq)]
+pub struct AssertResult {
+    /// Rows which did not match, ordered by row. Every row is listed once with
+    /// all of its mismatches, whatever `Options::max_threads` is.
+    pub failed_rows: Vec<FailedRow>,
+    /// Records which could not be parsed and have been skipped.
+    pub malformed: Vec<MalformedRecord>,
+    /// Records which have been skipped in flexible mode because of their width.
+    pub ragged: Vec<RaggedRecord>,
+}
+
+/// A row with at least one value which does not match the expected type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailedRow {
+    /// Index of the row starting at 0. The header and skipped records are not
+    /// counted.
+    pub row: usize,
+    /// Number of the record starting at 1, the header included.
+    pub record: u64,
+    /// Line the record starts on, starting at 1. This differs from `record`
+    /// if quoted fields contain line breaks.
+    pub line: u64,
+    /// Byte offset of the record from the start of the input.
+    pub byte: u64,
+    /// Values which did not match, ordered by column.
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl FailedRow {
+    fn new(row: usize, record: &csv::ByteRecord, mismatches: Vec<Mismatch>) -> Self {
+        let position = position(record);
+        Self {
+            row,
+            record: position.record() + 1,
+            line: position.line(),
+            byte: position.byte(),
+            mismatches,
+        }
+    }
 }
 
+/// Position of a record read by `reader::Records`.
+fn position(record: &csv::ByteRecord) -> csv::Position {
+    record
+        .position()
+        .cloned()
+        .unwrap_or_else(csv::Position::new)
+}
+
+/// A value which does not match the expected type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    /// Index of the row, see `FailedRow::row`.
+    pub row: usize,
+    /// Index of the column starting at 0.
+    pub column: usize,
+    /// Name of the column if `Options::has_headers` is set.
+    pub header: Option<String>,
+    /// The value which did not match. Invalid UTF-8 is replaced.
+    pub value: String,
+    /// Name of the expected type.
+    pub expected: String,
+}
+
+/// A record which could not be parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MalformedRecord {
+    /// Number of the record starting at 1, the header included.
+    pub record: u64,
+    /// Line the record starts on, starting at 1.
+    pub line: u64,
+    /// Byte offset of the record from the start of the input.
+    pub byte: u64,
+    /// Description of what is wrong with the record.
+    pub kind: String,
+}
+
+impl MalformedRecord {
+    /// Returns `None` for errors which are not caused by a single record.
+    fn from_csv_error(err: &csv::Error) -> Option<Self> {
+        let (position, kind) = match err.kind() {
+            csv::ErrorKind::Utf8 { pos, err } => (
+                pos.as_ref(),
+                format!("invalid UTF-8 in field {}", err.field() + 1),
+            ),
+            csv::ErrorKind::UnequalLengths {
+                pos,
+                expected_len,
+                len,
+            } => (
+                pos.as_ref(),
+                format!("expected {} fields, found {}", expected_len, len),
+            ),
+            _ => return None,
+        };
+        let position = position?;
+        Some(Self {
+            record: position.record() + 1,
+            line: position.line(),
+            byte: position.byte(),
+            kind,
+        })
+    }
+}
+
+/// A record with the wrong number of fields.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RaggedRecord {
+    /// Number of the record starting at 1, the header included.
+    pub record: u64,
+    /// Line the record starts on, starting at 1.
+    pub line: u64,
+    /// Byte offset of the record from the start of the input.
+    pub byte: u64,
+    /// Number of fields of the header or the first record, or the number of
+    /// expected types for `assert_columns_match`.
+    pub expected: usize,
+    pub found: usize,
+}
+
+/// What to do with records which can not be parsed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ErrorPolicy {
+    /// Stop with `Error::Malformed`.
+    #[default]
+    Fail,
+    /// Skip the record. It is reported so a warning can be shown.
+    Skip,
+    /// Skip the record and report it as part of the result.
+    Collect,
+}
+
+#[derive(Clone, Default)]
 pub struct Options {
     pub has_headers: bool,
+    /// Number of worker threads. Defaults to the number of available CPUs.
     pub max_threads: Option<usize>,
+    /// Format of the CSV data. Not used for `CsvInput::Reader`.
+    pub dialect: dialect::Dialect,
+    pub on_error: ErrorPolicy,
+    /// Skip records with a different number of fields and report them as
+    /// `RaggedRecord`s instead of treating them as malformed.
+    pub flexible: bool,
+    /// Encoding of `CsvInput::Read`. Other inputs are already UTF-8.
+    pub encoding: encoding::Encoding,
+    /// Match the raw bytes of every field without checking for valid UTF-8.
+    /// Type patterns are matched with `regex::bytes`.
+    pub raw_bytes: bool,
+    /// Values which stand for a missing value, like an empty string or `NA`.
+    /// They are not matched against the types of `get_types`, but counted as
+    /// nulls. In `assert_columns_match` nullable types accept them.
+    pub null_tokens: Vec<String>,
+    /// Makes `get_types` report types which matched at least this share of the
+    /// values which are not null, between 0 and 1. The values which did not
+    /// match are reported as `Column::outliers`. By default a type has to match
+    /// every value.
+    pub threshold: Option<f64>,
+    /// Makes `get_types` collect `Column::stats`. Every value is checked
+    /// against every type, which is slower.
+    pub stats: bool,
+    /// Remembers the result of up to this many distinct values per column, so
+    /// repeated values are only checked once.
+    pub cache_size: Option<usize>,
 }
 
 #[derive(PartialEq, Debug)]
 pub enum Error {
     Join,
     ThreadCount,
-    ColumnCountNotMatching,
+    /// The number of expected types is not the number of columns.
+    ColumnCountNotMatching {
+        expected: usize,
+        found: usize,
+    },
+    /// A record could not be parsed and `ErrorPolicy::Fail` is used.
+    Malformed(MalformedRecord),
+    /// The input could not be read.
+    Read(String),
+    /// The type of this name is its own ancestor.
+    ParentCycle(String),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::ColumnCountNotMatching => write!(f, "Column count not matching"),
+            Self::ColumnCountNotMatching { expected, found } => write!(
+                f,
+                "Column count not matching, expected {} columns, found {}",
+                expected, found
+            ),
             Self::Join => write!(f, "Could not join threads"),
             Self::ThreadCount => write!(f, "Thread smaller then one"),
+            Self::Malformed(record) => write!(
+                f,
+                "Malformed record {} (line {}, byte {}): {}",
+                record.record, record.line, record.byte, record.kind
+            ),
+            Self::Read(err) => write!(f, "Could not read input: {}", err),
+            Self::ParentCycle(name) => write!(f, "Type {} is its own ancestor", name),
         }
     }
 }
@@ -101,8 +468,8 @@ mod tests {
 
     #[test]
     fn get_types_get_types() {
-        let type1 = types::Type::new("T1", "^1$");
-        let type2 = types::Type::new("T2", "^2$");
+        let type1 = types::Type::new("T1", "^1$").with_parent("Td");
+        let type2 = types::Type::new("T2", "^2$").with_parent("Td");
         let typed = types::Type::new("Td", r"^\d$");
         let types = types::TypeList::from(vec![type1.clone(), type2.clone(), typed.clone()]);
         let ret = get_types(
@@ -111,11 +478,13 @@ mod tests {
             Options {
                 has_headers: false,
                 max_threads: Some(1),
+                ..Options::default()
             },
         )
         .map(|c| {
-            c.1.into_iter()
-                .map(|c| c.into_iter().map(|t| t.name).collect::<Vec<_>>())
+            c.columns
+                .into_iter()
+                .map(|c| c.types.into_iter().map(|t| t.name).collect::<Vec<_>>())
                 .collect::<Vec<_>>()
         });
         let expected = vec![
@@ -127,10 +496,22 @@ mod tests {
         assert_eq!(Ok(expected), ret);
     }
 
+    #[test]
+    fn get_types_parent_cycle() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("a", ".*").with_parent("b"),
+            types::Type::new("b", ".*").with_parent("a"),
+        ]);
+        assert_eq!(
+            Some(Error::ParentCycle(String::from("a"))),
+            get_types(CsvInput::Csv("1"), types, Options::default()).err()
+        );
+    }
+
     #[test]
     fn get_types_get_types_multi_threads() {
-        let type1 = types::Type::new("T1", "^1$");
-        let type2 = types::Type::new("T2", "^2$");
+        let type1 = types::Type::new("T1", "^1$").with_parent("Td");
+        let type2 = types::Type::new("T2", "^2$").with_parent("Td");
         let typed = types::Type::new("Td", r"^\d$");
         let types = types::TypeList::from(vec![type1.clone(), type2.clone(), typed.clone()]);
         let ret = get_types(
@@ -139,11 +520,13 @@ mod tests {
             Options {
                 has_headers: false,
                 max_threads: Some(2),
+                ..Options::default()
             },
         )
         .map(|c| {
-            c.1.into_iter()
-                .map(|c| c.into_iter().map(|t| t.name).collect::<Vec<_>>())
+            c.columns
+                .into_iter()
+                .map(|c| c.types.into_iter().map(|t| t.name).collect::<Vec<_>>())
                 .collect::<Vec<_>>()
         });
         let expected = vec![
@@ -155,6 +538,179 @@ mod tests {
         assert_eq!(Ok(expected), ret);
     }
 
+    #[test]
+    fn get_types_many_rows_multi_threads() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d+"),
+        ]);
+        let mut csv = (0..5000)
+            .map(|i| format!("{},{}\n", i, i))
+            .collect::<String>();
+        csv.push_str("1,x\n");
+        let ret = get_types(
+            CsvInput::Csv(&csv),
+            types,
+            Options {
+                has_headers: false,
+                max_threads: Some(3),
+                ..Options::default()
+            },
+        )
+        .map(|c| {
+            c.columns
+                .into_iter()
+                .map(|c| c.types.into_iter().map(|t| t.name).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        });
+        let expected = vec![
+            vec!["num".to_owned(), "str".to_owned()],
+            vec!["str".to_owned()],
+        ];
+        assert_eq!(Ok(expected), ret);
+    }
+
+    #[test]
+    fn get_types_with_dialect() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d+"),
+        ]);
+        let ret = get_types(
+            CsvInput::Csv("1;a,b\n2;c"),
+            types,
+            Options {
+                dialect: dialect::Dialect {
+                    delimiter: b';',
+                    ..dialect::Dialect::default()
+                },
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(2, ret.columns.len());
+        assert_eq!(2, ret.columns[0].types.len());
+    }
+
+    #[test]
+    fn get_types_fails_on_malformed_record() {
+        let types = types::TypeList::from(vec![types::Type::new("str", ".*")]);
+        let ret = get_types(CsvInput::Csv("a,b\nc\nd,e"), types, Options::default());
+        match ret {
+            Err(Error::Malformed(record)) => assert_eq!((2, 2), (record.record, record.line)),
+            _ => panic!("expected a malformed record"),
+        }
+    }
+
+    #[test]
+    fn assert_columns_match_collects_malformed_records() {
+        let expected_types = vec![
+            types::Type::new("int", r"\d+"),
+            types::Type::new("int", r"\d+"),
+        ];
+        let ret = assert_columns_match(
+            CsvInput::Csv("1,2\n3\nx,4"),
+            expected_types,
+            Options {
+                on_error: ErrorPolicy::Collect,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            vec![FailedRow {
+                row: 1,
+                record: 3,
+                line: 3,
+                byte: 6,
+                mismatches: vec![Mismatch {
+                    row: 1,
+                    column: 0,
+                    header: None,
+                    value: String::from("x"),
+                    expected: String::from("int"),
+                }]
+            }],
+            ret.failed_rows
+        );
+        assert_eq!(1, ret.malformed.len());
+        assert_eq!(2, ret.malformed[0].record);
+    }
+
+    #[test]
+    fn get_types_flexible_uses_rows_with_expected_width() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d+"),
+        ]);
+        let ret = get_types(
+            CsvInput::Csv("h1,h2\n1,2\nx\n3,4,y\n5,6"),
+            types,
+            Options {
+                has_headers: true,
+                flexible: true,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(2, ret.columns.len());
+        assert!(ret.columns.iter().all(|col| col.types.len() == 2));
+        assert_eq!(
+            vec![(3, 2, 1), (4, 2, 3)],
+            ret.ragged
+                .iter()
+                .map(|r| (r.record, r.expected, r.found))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn assert_columns_match_flexible() {
+        let expected_types = vec![
+            types::Type::new("int", r"\d+"),
+            types::Type::new("int", r"\d+"),
+        ];
+        let ret = assert_columns_match(
+            CsvInput::Csv("1\n1,2\n3,x,5"),
+            expected_types,
+            Options {
+                flexible: true,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert!(ret.failed_rows.is_empty());
+        assert_eq!(
+            vec![1, 3],
+            ret.ragged.iter().map(|r| r.record).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn assert_columns_match_flexible_header_of_other_width() {
+        let expected_types = vec![
+            types::Type::new("int", r"\d+"),
+            types::Type::new("int", r"\d+"),
+        ];
+        let ret = assert_columns_match(
+            CsvInput::Csv("h1,h2,h3\n1,2\nx,4"),
+            expected_types,
+            Options {
+                has_headers: true,
+                flexible: true,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert!(ret.ragged.is_empty());
+        assert_eq!(1, ret.failed_rows.len());
+        assert_eq!(3, ret.failed_rows[0].record);
+        assert_eq!(
+            Some(String::from("h1")),
+            ret.failed_rows[0].mismatches[0].header
+        );
+    }
+
     #[test]
     fn get_types_thread_count_error() {
         let type_def = types::Type::new("test", "^.*$");
@@ -165,24 +721,277 @@ mod tests {
             Options {
                 has_headers: false,
                 max_threads: Some(0),
+                ..Options::default()
             },
         ) {
-            Ok(_) => assert!(false),
-            Err(e) => match e {
-                Error::ThreadCount => assert!(true),
-                _ => assert!(false),
-            },
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(Error::ThreadCount, e),
         };
     }
 
     #[test]
     fn get_header_success() {
         let mut input = vec![
-            vec!["h1".to_owned(), "h2".to_owned()],
-            vec!["v1".to_owned(), "v2".to_owned()],
-        ];
+            csv::ByteRecord::from(vec!["h1", "h2"]),
+            csv::ByteRecord::from(vec!["v1", "v2"]),
+        ]
+        .into_iter();
         let h = get_header(&mut input);
         assert_eq!(vec!("h1".to_owned(), "h2".to_owned()), h);
-        assert_eq!(vec!(vec!("v1".to_owned(), "v2".to_owned())), input);
+        assert_eq!(
+            vec!(csv::ByteRecord::from(vec!["v1", "v2"])),
+            input.collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn get_types_with_header() {
+        let types = types::TypeList::from(vec![types::Type::new("Td", r"\d")]);
+        let ret = get_types(
+            CsvInput::Csv("h1,h2\n1,2\n3,4"),
+            types,
+            Options {
+                has_headers: true,
+                max_threads: None,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(vec!["h1".to_owned(), "h2".to_owned()], ret.headers);
+        assert_eq!(2, ret.columns.len());
+        assert!(ret.columns.iter().all(|col| col.types.len() == 1));
+    }
+
+    #[test]
+    fn get_types_header_after_bom() {
+        let ret = get_types(
+            CsvInput::Read(Box::new(&b"\xef\xbb\xbfid,name\n1,x"[..])),
+            types::TypeList::from(vec![types::Type::new("string", ".*")]),
+            Options {
+                has_headers: true,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(vec!["id".to_owned(), "name".to_owned()], ret.headers);
+    }
+
+    #[test]
+    fn assert_columns_match_with_encoding_and_raw_bytes() {
+        let expected_types = vec![types::Type::new("city", r"M\xfcnchen")];
+        let options = Options {
+            encoding: encoding::Encoding::for_label("windows-1252").unwrap(),
+            ..Options::default()
+        };
+        let ret = assert_columns_match(
+            CsvInput::Read(Box::new(&b"M\xfcnchen"[..])),
+            expected_types.clone(),
+            options,
+        );
+        assert!(ret.unwrap().failed_rows.is_empty());
+
+        let ret = assert_columns_match(
+            CsvInput::Read(Box::new(&b"M\xfcnchen"[..])),
+            expected_types.clone(),
+            Options::default(),
+        );
+        assert!(matches!(ret, Err(Error::Malformed(_))));
+
+        let options = Options {
+            raw_bytes: true,
+            ..Options::default()
+        };
+        let ret = assert_columns_match(
+            CsvInput::Read(Box::new(&b"M\xfcnchen"[..])),
+            expected_types,
+            options,
+        );
+        assert!(ret.unwrap().failed_rows.is_empty());
+    }
+
+    #[test]
+    fn assert_columns_match_reports_positions() {
+        let expected_types = vec![
+            types::Type::new("str", "(?s).*"),
+            types::Type::new("int", r"\d+"),
+        ];
+        let ret = assert_columns_match(
+            CsvInput::Csv("h1,h2\n\"a\nb\",1\nc,x\n"),
+            expected_types,
+            Options {
+                has_headers: true,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            vec![FailedRow {
+                row: 1,
+                record: 3,
+                line: 4,
+                byte: 14,
+                mismatches: vec![Mismatch {
+                    row: 1,
+                    column: 1,
+                    header: Some(String::from("h2")),
+                    value: String::from("x"),
+                    expected: String::from("int"),
+                }]
+            }],
+            ret.failed_rows
+        );
+    }
+
+    #[test]
+    fn get_types_with_null_tokens() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d+"),
+        ]);
+        let ret = get_types(
+            CsvInput::Csv("1,a\nNA,b\n3,c\n,d"),
+            types,
+            Options {
+                null_tokens: vec![String::new(), String::from("NA")],
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        let column = &ret.columns[0];
+        assert_eq!(
+            vec!["num?", "str?"],
+            column
+                .types
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(0.5, column.null_ratio());
+        assert_eq!(0.0, ret.columns[1].null_ratio());
+        assert!(!ret.columns[1].types[0].nullable);
+    }
+
+    #[test]
+    fn get_types_sorted_by_specificity() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("string", ".*"),
+            types::Type::new("float", r"\d+(\.\d+)?").with_parent("string"),
+            types::Type::new("int", r"\d+").with_parent("float"),
+        ]);
+        let ret = get_types(CsvInput::Csv("1,1.5,x"), types, Options::default()).unwrap();
+        assert_eq!(
+            vec![
+                vec!["int", "float", "string"],
+                vec!["float", "string"],
+                vec!["string"]
+            ],
+            ret.columns
+                .iter()
+                .map(|c| c.types.iter().map(|t| t.name.as_str()).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("int", ret.columns[0].best_type().unwrap().name);
+    }
+
+    #[test]
+    fn get_types_with_threshold() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d+").with_parent("str"),
+        ]);
+        let mut csv = (0..99)
+            .map(|i| format!("{},{}\n", i, i))
+            .collect::<String>();
+        csv.push_str("x,\ny,z\n");
+        let ret = get_types(
+            CsvInput::Csv(&csv),
+            types,
+            Options {
+                threshold: Some(0.98),
+                null_tokens: vec![String::new()],
+                max_threads: Some(2),
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        let column = &ret.columns[0];
+        assert_eq!(vec!["num", "str"], names_of(column));
+        assert_eq!(vec![99, 101], column.matched);
+        assert_eq!(
+            vec![(99, "x"), (100, "y")],
+            column
+                .outliers
+                .iter()
+                .map(|o| (o.row, o.value.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["num"], column.outliers[0].types);
+        assert_eq!(
+            (101, 101),
+            (column.outliers[1].record, column.outliers[1].line)
+        );
+
+        // 99 of 100 values which are not null are numbers.
+        assert_eq!(vec!["num?", "str?"], names_of(&ret.columns[1]));
+        assert_eq!(0.99, ret.columns[1].match_ratio(0));
+    }
+
+    #[test]
+    fn get_types_with_stats() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d+").with_parent("str"),
+        ]);
+        let csv = "a,b\n1,x\n22,\n1,NA\nüber,\n";
+        let ret = get_types(
+            CsvInput::Csv(csv),
+            types,
+            Options {
+                has_headers: true,
+                stats: true,
+                null_tokens: vec![String::from("NA")],
+                max_threads: Some(2),
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        // The reported types are the same as without stats.
+        assert_eq!(vec!["str"], names_of(&ret.columns[0]));
+        let stats = ret.columns[0].stats.as_ref().unwrap();
+        assert_eq!(
+            (0, 3, false),
+            (stats.empties, stats.distinct, stats.distinct_overflow)
+        );
+        assert_eq!((Some(1), Some(4)), (stats.min_length, stats.max_length));
+        assert_eq!(
+            vec![("str", 4), ("num", 3)],
+            stats
+                .types
+                .iter()
+                .map(|t| (t.name.as_str(), t.matched))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(None, stats.types[0].first_failure);
+        let failure = stats.types[1].first_failure.as_ref().unwrap();
+        assert_eq!(("über", 5), (failure.value.as_str(), failure.line));
+
+        let stats = ret.columns[1].stats.as_ref().unwrap();
+        assert_eq!((1, 2), (ret.columns[1].nulls, stats.empties));
+        assert_eq!((2, Some(0)), (stats.distinct, stats.min_length));
+        assert!(get_types(
+            CsvInput::Csv(csv),
+            types::TypeList::new(),
+            Options::default()
+        )
+        .unwrap()
+        .columns[0]
+            .stats
+            .is_none());
+    }
+
+    fn names_of(column: &Column) -> Vec<String> {
+        column.types.iter().map(|t| t.to_string()).collect()
     }
 }
@@ -0,0 +1,1017 @@
+//! Checks which decide if a value has a type.
+//!
+//! Types of the config are regexes, but library users can add their own
+//! `TypeMatcher` for values which can not be checked with a regex, like
+//! identifiers with a checksum or real dates.
+
+use super::native::{self, Native};
+use chrono::format::{Fixed, Item, StrftimeItems};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use regex::bytes;
+use regex::Regex;
+use std::fmt::{self, Write};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+pub trait TypeMatcher: fmt::Debug + Send + Sync {
+    /// Checks a value which is valid UTF-8.
+    fn is_match(&self, value: &str) -> bool;
+
+    /// Checks the raw bytes of a value, see `Options::raw_bytes`. By default
+    /// only valid UTF-8 is checked with `is_match`.
+    fn is_match_bytes(&self, value: &[u8]) -> bool {
+        std::str::from_utf8(value).is_ok_and(|value| self.is_match(value))
+    }
+
+    /// Describes which values match, like the regex. Types with the same name,
+    /// description and parent are equal.
+    fn description(&self) -> &str;
+
+    /// The anchored pattern if the matcher is nothing but a regex, so it can be
+    /// checked together with others in a `types::TypeSet`.
+    fn regex(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Matches the whole value with a regex.
+#[derive(Debug, Clone)]
+pub struct RegexMatcher {
+    pattern: Regex,
+    /// Same as `pattern` but matches raw bytes. Unicode is disabled unless the
+    /// pattern contains non-ASCII characters, so `.` matches any byte, `\xfc`
+    /// matches the byte 0xFC and classes like `\d` only match ASCII.
+    bytes_pattern: bytes::Regex,
+    /// Used instead of the patterns where it gives the same result.
+    native: Option<Native>,
+}
+
+impl RegexMatcher {
+    /// The pattern has to match the whole value, also with alternatives like
+    /// `100|[1-9]?[0-9]`.
+    pub fn new(pattern: &str) -> Self {
+        let pattern = format!("^(?:{})$", pattern);
+        Self {
+            pattern: Regex::new(&pattern).unwrap(),
+            bytes_pattern: bytes::RegexBuilder::new(&pattern)
+                .unicode(false)
+                .build()
+                .or_else(|_| bytes::Regex::new(&pattern))
+                .unwrap(),
+            native: None,
+        }
+    }
+
+    /// Matches without a regex where possible. The patterns are the ones of
+    /// `Native::pattern`.
+    pub fn from_native(native: Native) -> Self {
+        Self {
+            native: Some(native),
+            ..Self::new(native.pattern())
+        }
+    }
+}
+
+impl TypeMatcher for RegexMatcher {
+    fn is_match(&self, value: &str) -> bool {
+        // Non-ASCII values need the Unicode classes of the regex.
+        match self.native {
+            Some(native) if value.is_ascii() => native.is_match(value.as_bytes()),
+            _ => self.pattern.is_match(value),
+        }
+    }
+
+    fn is_match_bytes(&self, value: &[u8]) -> bool {
+        match self.native {
+            Some(native) => native.is_match(value),
+            None => self.bytes_pattern.is_match(value),
+        }
+    }
+
+    fn description(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    fn regex(&self) -> Option<&str> {
+        match self.native {
+            Some(_) => None,
+            None => Some(self.pattern.as_str()),
+        }
+    }
+}
+
+/// Matches the values a closure accepts.
+pub struct FnMatcher<F> {
+    description: String,
+    check: F,
+}
+
+impl<F> FnMatcher<F>
+where
+    F: Fn(&str) -> bool + Send + Sync,
+{
+    pub fn new(description: &str, check: F) -> Self {
+        Self {
+            description: description.to_owned(),
+            check,
+        }
+    }
+}
+
+impl<F> fmt::Debug for FnMatcher<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnMatcher")
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+impl<F> TypeMatcher for FnMatcher<F>
+where
+    F: Fn(&str) -> bool + Send + Sync,
+{
+    fn is_match(&self, value: &str) -> bool {
+        (self.check)(value)
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// Matches the values which can be parsed into `T`, like `ParseMatcher::<f64>`.
+pub struct ParseMatcher<T> {
+    description: String,
+    target: PhantomData<fn() -> T>,
+}
+
+impl<T: FromStr> ParseMatcher<T> {
+    pub fn new() -> Self {
+        Self {
+            description: format!("parse {}", std::any::type_name::<T>()),
+            target: PhantomData,
+        }
+    }
+}
+
+impl<T: FromStr> Default for ParseMatcher<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for ParseMatcher<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParseMatcher")
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+impl<T: FromStr> TypeMatcher for ParseMatcher<T> {
+    fn is_match(&self, value: &str) -> bool {
+        value.parse::<T>().is_ok()
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// Conventions which are tried by `types::number_locales`, as name, decimal
+/// separator and grouping separator.
+pub const NUMBER_LOCALES: &[(&str, char, char)] = &[
+    ("en", '.', ','),
+    ("de", ',', '.'),
+    ("fr", ',', ' '),
+    ("ch", '.', '\''),
+];
+
+/// Matches numbers written with a decimal separator and an optional grouping
+/// separator between every three digits, like `1.234,56` or `1 234,56`. An
+/// ungrouped integer part like `1234,56` is accepted as well. A space as
+/// grouping separator also accepts the no-break spaces U+00A0 and U+202F.
+#[derive(Debug, Clone)]
+pub struct NumberMatcher {
+    decimal: char,
+    grouping: Option<char>,
+    description: String,
+}
+
+impl NumberMatcher {
+    /// Returns `None` if the separators are digits, signs or the same.
+    pub fn new(decimal: char, grouping: Option<char>) -> Option<Self> {
+        let invalid = |c: char| c.is_ascii_digit() || c == '-' || c == '+';
+        if invalid(decimal) || grouping.is_some_and(|g| invalid(g) || g == decimal) {
+            return None;
+        }
+        Some(Self {
+            decimal,
+            grouping,
+            description: format!(
+                "number({}{})",
+                decimal,
+                grouping.map_or(String::new(), String::from)
+            ),
+        })
+    }
+
+    fn is_grouping(&self, c: char) -> bool {
+        match self.grouping {
+            Some(' ') => c == ' ' || c == '\u{a0}' || c == '\u{202f}',
+            Some(grouping) => c == grouping,
+            None => false,
+        }
+    }
+
+    fn is_integer_part(&self, value: &str) -> bool {
+        let is_digits = |digits: &str| digits.bytes().all(|b| b.is_ascii_digit());
+        let mut groups = value.split(|c| self.is_grouping(c));
+        let first = groups.next().unwrap_or("");
+        let mut rest = groups.peekable();
+        if rest.peek().is_none() {
+            return is_digits(first);
+        }
+        (1..=3).contains(&first.len())
+            && is_digits(first)
+            && rest.all(|group| group.len() == 3 && is_digits(group))
+    }
+}
+
+impl TypeMatcher for NumberMatcher {
+    fn is_match(&self, value: &str) -> bool {
+        let number = value
+            .strip_prefix('-')
+            .or_else(|| value.strip_prefix('+'))
+            .unwrap_or(value);
+        let (integer, fraction) = match number.split_once(self.decimal) {
+            Some((integer, fraction)) => (integer, fraction),
+            None => (number, ""),
+        };
+        (!integer.is_empty() || !fraction.is_empty())
+            && self.is_integer_part(integer)
+            && fraction.bytes().all(|b| b.is_ascii_digit())
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// A check of the parsed value written like `int(0..360)`, see
+/// `ParamMatcher::parse`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Param {
+    /// Whole numbers between the bounds, both included.
+    Int(Option<i64>, Option<i64>),
+    /// Numbers like `Native::Float` between the bounds, both included.
+    Float(Option<f64>, Option<f64>),
+    /// Strings of at most this many characters.
+    Varchar(usize),
+    /// Numbers with at most `precision` digits, `scale` of them after the point.
+    Decimal { precision: usize, scale: usize },
+}
+
+/// Matches the values which fit a `Param`, like a wind direction of
+/// `int(0..360)` which rejects `540`.
+#[derive(Debug, Clone)]
+pub struct ParamMatcher {
+    param: Param,
+    description: String,
+}
+
+impl ParamMatcher {
+    pub fn new(param: Param) -> Self {
+        let bound = |bound: Option<String>| bound.unwrap_or_default();
+        let description = match param {
+            Param::Int(min, max) => format!(
+                "int({}..{})",
+                bound(min.map(|m| m.to_string())),
+                bound(max.map(|m| m.to_string()))
+            ),
+            Param::Float(min, max) => format!(
+                "float({}..{})",
+                bound(min.map(|m| m.to_string())),
+                bound(max.map(|m| m.to_string()))
+            ),
+            Param::Varchar(length) => format!("varchar({})", length),
+            Param::Decimal { precision, scale } => format!("decimal({},{})", precision, scale),
+        };
+        Self { param, description }
+    }
+
+    /// Parses `int(min..max)`, `float(min..max)`, `varchar(length)` or
+    /// `decimal(precision,scale)`. One of the bounds of a range may be left out,
+    /// like `int(0..)`. Returns `None` for other definitions or invalid
+    /// parameters.
+    pub fn parse(definition: &str) -> Option<Self> {
+        let (kind, params) = definition.strip_suffix(')')?.split_once('(')?;
+        let param = match kind.trim() {
+            "int" => {
+                let (min, max) = parse_range::<i64>(params)?;
+                Param::Int(min, max)
+            }
+            "float" => {
+                let (min, max) = parse_range::<f64>(params)?;
+                Param::Float(min, max)
+            }
+            "varchar" => Param::Varchar(params.trim().parse().ok()?),
+            "decimal" => {
+                let (precision, scale) = params.split_once(',')?;
+                let precision = precision.trim().parse().ok()?;
+                let scale = scale.trim().parse().ok()?;
+                if precision == 0 || scale > precision {
+                    return None;
+                }
+                Param::Decimal { precision, scale }
+            }
+            _ => return None,
+        };
+        Some(Self::new(param))
+    }
+
+    pub fn param(&self) -> Param {
+        self.param
+    }
+}
+
+fn parse_range<T: FromStr + PartialOrd>(range: &str) -> Option<(Option<T>, Option<T>)> {
+    let (min, max) = range.split_once("..")?;
+    let bound = |bound: &str| match bound.trim() {
+        "" => Some(None),
+        // A NaN bound can not be compared with any value.
+        bound => bound
+            .parse::<T>()
+            .ok()
+            .filter(|b| b.partial_cmp(b).is_some())
+            .map(Some),
+    };
+    match (bound(min)?, bound(max)?) {
+        (None, None) => None,
+        (Some(min), Some(max)) if min > max => None,
+        range => Some(range),
+    }
+}
+
+fn in_range<T: PartialOrd>(value: T, min: Option<T>, max: Option<T>) -> bool {
+    min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+}
+
+impl TypeMatcher for ParamMatcher {
+    fn is_match(&self, value: &str) -> bool {
+        match self.param {
+            Param::Int(min, max) => {
+                native::is_int(value.as_bytes())
+                    && value.parse::<i64>().is_ok_and(|v| in_range(v, min, max))
+            }
+            Param::Float(min, max) => {
+                native::is_float(value.as_bytes())
+                    && value.parse::<f64>().is_ok_and(|v| in_range(v, min, max))
+            }
+            Param::Varchar(length) => value.chars().count() <= length,
+            Param::Decimal { precision, scale } => {
+                if !native::is_float(value.as_bytes()) {
+                    return false;
+                }
+                let number = value.trim_start_matches(['-', '+']);
+                let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+                integer.trim_start_matches('0').len() <= precision - scale
+                    && fraction.len() <= scale
+            }
+        }
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// Numbers which fit a fixed width type, see `types::width_types`. Integers
+/// have to be in the range of the type. Floats have to be finite and written
+/// with no more digits than the type keeps, so `0.1` is an `f32` but
+/// `0.123456789` is not.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Width {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+}
+
+impl Width {
+    /// Ordered so that for types with the same specificity the last one is the
+    /// narrowest integer type, see `TypeList::sort_by_specificity`.
+    pub const ALL: [Width; 10] = [
+        Self::F64,
+        Self::F32,
+        Self::I64,
+        Self::I32,
+        Self::I16,
+        Self::I8,
+        Self::U64,
+        Self::U32,
+        Self::U16,
+        Self::U8,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::I8 => "i8",
+            Self::I16 => "i16",
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::U8 => "u8",
+            Self::U16 => "u16",
+            Self::U32 => "u32",
+            Self::U64 => "u64",
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+        }
+    }
+
+    /// The next wider type, which matches every value of this one.
+    pub fn parent(self) -> &'static str {
+        match self {
+            Self::I8 => "i16",
+            Self::I16 => "i32",
+            Self::I32 => "i64",
+            Self::U8 => "u16",
+            Self::U16 => "u32",
+            Self::U32 => "u64",
+            Self::I64 | Self::U64 => "int",
+            Self::F32 => "f64",
+            Self::F64 => "float",
+        }
+    }
+
+    /// Decimal digits which survive a round trip through a float type, the
+    /// largest decimal exponent of its normal numbers and the largest whole
+    /// number up to which all whole numbers are exact.
+    fn float_precision(self) -> (usize, i64, i128) {
+        match self {
+            Self::F32 => (
+                f32::DIGITS as usize,
+                f32::MAX_10_EXP.into(),
+                1 << f32::MANTISSA_DIGITS,
+            ),
+            _ => (
+                f64::DIGITS as usize,
+                f64::MAX_10_EXP.into(),
+                1 << f64::MANTISSA_DIGITS,
+            ),
+        }
+    }
+
+    /// Smallest and largest value of an integer type.
+    fn range(self) -> Option<(i128, i128)> {
+        match self {
+            Self::I8 => Some((i8::MIN.into(), i8::MAX.into())),
+            Self::I16 => Some((i16::MIN.into(), i16::MAX.into())),
+            Self::I32 => Some((i32::MIN.into(), i32::MAX.into())),
+            Self::I64 => Some((i64::MIN.into(), i64::MAX.into())),
+            Self::U8 => Some((0, u8::MAX.into())),
+            Self::U16 => Some((0, u16::MAX.into())),
+            Self::U32 => Some((0, u32::MAX.into())),
+            Self::U64 => Some((0, u64::MAX.into())),
+            Self::F32 | Self::F64 => None,
+        }
+    }
+}
+
+/// Parses a value like `native::is_int`, faster than `str::parse` for `i128`.
+/// Returns `None` if the value does not fit into `u64`.
+fn parse_int(value: &str) -> Option<i128> {
+    let digits = value.trim_start_matches(['-', '+']);
+    let mut magnitude: u64 = 0;
+    for digit in digits.bytes() {
+        magnitude = magnitude
+            .checked_mul(10)?
+            .checked_add(u64::from(digit - b'0'))?;
+    }
+    if value.starts_with('-') {
+        Some(-i128::from(magnitude))
+    } else {
+        Some(i128::from(magnitude))
+    }
+}
+
+/// Number of significant digits and decimal exponent of a number like
+/// `native::is_float`, like `scientific` without allocating.
+fn significant_digits(number: &str) -> (usize, i64) {
+    let number = number.trim_start_matches(['-', '+']).as_bytes();
+    let point = number
+        .iter()
+        .position(|&b| b == b'.')
+        .unwrap_or(number.len());
+    let first = number.iter().position(|&b| b != b'0' && b != b'.');
+    let last = number.iter().rposition(|&b| b != b'0' && b != b'.');
+    match (first, last) {
+        (Some(first), Some(last)) => {
+            let digits = last + 1 - first - usize::from(first < point && point < last);
+            let exponent = if first < point {
+                (point - first) as i64 - 1
+            } else {
+                point as i64 - first as i64
+            };
+            (digits, exponent)
+        }
+        _ => (0, 0),
+    }
+}
+
+/// Significant digits and decimal exponent of a number like `native::is_float`
+/// or formatted with `{:e}`, so `0.0120` and `1.2e-2` both give `("12", -2)`.
+fn scientific(number: &str) -> (String, i64) {
+    let (mantissa, exponent) = match number.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse().unwrap_or(0)),
+        None => (number, 0),
+    };
+    let mantissa = mantissa.trim_start_matches(['-', '+']);
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", integer, fraction);
+    let leading = digits.len() - digits.trim_start_matches('0').len();
+    let significant = digits.trim_matches('0');
+    if significant.is_empty() {
+        return (String::from("0"), 0);
+    }
+    let exponent = exponent + integer.len() as i64 - leading as i64 - 1;
+    (significant.to_owned(), exponent)
+}
+
+impl TypeMatcher for Width {
+    fn is_match(&self, value: &str) -> bool {
+        if let Some((min, max)) = self.range() {
+            return native::is_int(value.as_bytes())
+                && parse_int(value).is_some_and(|v| min <= v && v <= max);
+        }
+        if !native::is_float(value.as_bytes()) {
+            return false;
+        }
+        let (digits, exponent) = significant_digits(value);
+        let (precision, max_exponent, max_exact) = self.float_precision();
+        if digits <= precision && exponent.abs() < max_exponent {
+            return true;
+        }
+        if native::is_int(value.as_bytes())
+            && parse_int(value).is_some_and(|v| v.abs() <= max_exact)
+        {
+            return true;
+        }
+        // The shortest representation of the parsed value has the same digits
+        // unless some of them were lost.
+        let formatted = match self {
+            Self::F32 => value
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .map(|v| format!("{:e}", v)),
+            _ => value
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .map(|v| format!("{:e}", v)),
+        };
+        formatted.is_some_and(|formatted| scientific(&formatted) == scientific(value))
+    }
+
+    fn description(&self) -> &str {
+        self.name()
+    }
+}
+
+/// What a `TemporalMatcher` parses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Temporal {
+    Date,
+    Time,
+    DateTime,
+}
+
+impl Temporal {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Date => "date",
+            Self::Time => "time",
+            Self::DateTime => "datetime",
+        }
+    }
+}
+
+/// Formats which are tried by `types::date_formats`. Day first and month first
+/// formats are both included, the values of a column decide between them.
+pub const DATE_FORMATS: &[(Temporal, &str)] = &[
+    (Temporal::Date, "%Y-%m-%d"),
+    (Temporal::Date, "%Y/%m/%d"),
+    (Temporal::Date, "%d.%m.%Y"),
+    (Temporal::Date, "%d.%m.%y"),
+    (Temporal::Date, "%d/%m/%Y"),
+    (Temporal::Date, "%m/%d/%Y"),
+    (Temporal::Date, "%d/%m/%y"),
+    (Temporal::Date, "%m/%d/%y"),
+    (Temporal::Date, "%d-%m-%Y"),
+    (Temporal::Date, "%m-%d-%Y"),
+    (Temporal::Date, "%d %b %Y"),
+    (Temporal::Date, "%b %d, %Y"),
+    (Temporal::Date, "%d %B %Y"),
+    (Temporal::Date, "%B %d, %Y"),
+    (Temporal::DateTime, "%Y-%m-%dT%H:%M:%S"),
+    (Temporal::DateTime, "%Y-%m-%dT%H:%M:%SZ"),
+    (Temporal::DateTime, "%Y-%m-%dT%H:%M:%S%:z"),
+    (Temporal::DateTime, "%Y-%m-%d %H:%M:%S"),
+    (Temporal::DateTime, "%Y-%m-%d %H:%M"),
+    (Temporal::DateTime, "%d.%m.%Y %H:%M:%S"),
+    (Temporal::DateTime, "%d.%m.%Y %H:%M"),
+    (Temporal::DateTime, "%d/%m/%Y %H:%M"),
+    (Temporal::DateTime, "%m/%d/%Y %H:%M"),
+];
+
+/// Matches dates and times which can be parsed with a strftime format like
+/// `%Y-%m-%d`, so `2023-02-30` or `25:61` do not match. Offsets like `%z` are
+/// checked but not kept.
+#[derive(Debug, Clone)]
+pub struct TemporalMatcher {
+    temporal: Temporal,
+    format: String,
+    items: Vec<Item<'static>>,
+    description: String,
+    strict: bool,
+}
+
+impl TemporalMatcher {
+    /// Returns `None` if the format is not valid.
+    pub fn new(temporal: Temporal, format: &str) -> Option<Self> {
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+            return None;
+        }
+        Some(Self {
+            temporal,
+            format: format.to_owned(),
+            items: StrftimeItems::new(format).map(Item::to_owned).collect(),
+            description: format!("{}({})", temporal.name(), format),
+            strict: false,
+        })
+    }
+
+    /// Only accepts values which look exactly like the format, with zero
+    /// padding and four digit years for `%Y`. Otherwise `2023-1-5` is a
+    /// `%Y-%m-%d` date and `01.02.23` is a `%d.%m.%Y` date in the year 23.
+    /// Fractions of seconds like `%.f` and offsets like `%z` may still be
+    /// written in any way the parser accepts, like `.5` or `+0200`.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Checks a parsed value by formatting it again. Only the text between
+    /// fractions of seconds and offsets is compared, see `skip_flexible`.
+    fn formats_as<F>(&self, value: &str, format: F) -> bool
+    where
+        F: Fn(&[Item<'static>], &mut String) -> fmt::Result,
+    {
+        if !self.strict {
+            return true;
+        }
+        let mut rest = value;
+        let mut expected = String::with_capacity(value.len());
+        for segment in self.items.split_inclusive(is_flexible) {
+            let (fixed, flexible) = match segment.split_last() {
+                Some((last, fixed)) if is_flexible(last) => (fixed, Some(last)),
+                _ => (segment, None),
+            };
+            expected.clear();
+            if format(fixed, &mut expected).is_err() {
+                return false;
+            }
+            rest = match rest.strip_prefix(&expected[..]) {
+                Some(rest) => rest,
+                None => return false,
+            };
+            if let Some(item) = flexible {
+                rest = skip_flexible(item, rest);
+            }
+        }
+        rest.is_empty()
+    }
+
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+}
+
+/// Items whose text does not follow from the parsed value. `%.f` writes `.5`
+/// as `.500` and `%#z` can not be formatted at all.
+fn is_flexible(item: &Item) -> bool {
+    matches!(
+        item,
+        Item::Fixed(Fixed::Nanosecond)
+            | Item::Fixed(Fixed::TimezoneOffset)
+            | Item::Fixed(Fixed::TimezoneOffsetColon)
+            | Item::Fixed(Fixed::TimezoneOffsetDoubleColon)
+            | Item::Fixed(Fixed::TimezoneOffsetTripleColon)
+            | Item::Fixed(Fixed::TimezoneOffsetColonZ)
+            | Item::Fixed(Fixed::TimezoneOffsetZ)
+            | Item::Fixed(Fixed::Internal(_))
+    )
+}
+
+/// Skips the text of a flexible item, which the parser has already checked.
+fn skip_flexible<'v>(item: &Item, value: &'v str) -> &'v str {
+    let digits = |value: &str| {
+        value
+            .bytes()
+            .take_while(|b| b.is_ascii_digit() || *b == b':')
+            .count()
+    };
+    let end = match item {
+        Item::Fixed(Fixed::Nanosecond) => match value.strip_prefix('.') {
+            Some(fraction) => 1 + digits(fraction),
+            None => 0,
+        },
+        _ if value.starts_with(['Z', 'z']) => 1,
+        _ if value.starts_with(['+', '-']) => 1 + digits(&value[1..]),
+        _ => digits(value),
+    };
+    &value[end..]
+}
+
+impl TypeMatcher for TemporalMatcher {
+    fn is_match(&self, value: &str) -> bool {
+        // chrono skips whitespace which other types do not accept.
+        if value.trim() != value && self.format.trim() == self.format {
+            return false;
+        }
+        let format = &self.format[..];
+        match self.temporal {
+            Temporal::Date => NaiveDate::parse_from_str(value, format).is_ok_and(|date| {
+                self.formats_as(value, |items, out| {
+                    write!(out, "{}", date.format_with_items(items.iter()))
+                })
+            }),
+            Temporal::Time => NaiveTime::parse_from_str(value, format).is_ok_and(|time| {
+                self.formats_as(value, |items, out| {
+                    write!(out, "{}", time.format_with_items(items.iter()))
+                })
+            }),
+            Temporal::DateTime => {
+                NaiveDateTime::parse_from_str(value, format).is_ok_and(|datetime| {
+                    self.formats_as(value, |items, out| {
+                        write!(out, "{}", datetime.format_with_items(items.iter()))
+                    })
+                })
+            }
+        }
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{check_value, get_matching_types, Type, TypeList};
+
+    fn luhn(value: &str) -> bool {
+        let digits = value.chars().rev().map(|c| c.to_digit(10));
+        let mut sum = 0;
+        for (i, digit) in digits.enumerate() {
+            let digit = match digit {
+                Some(digit) => digit,
+                None => return false,
+            };
+            sum += match (i % 2, digit * 2) {
+                (0, _) => digit,
+                (_, doubled) if doubled > 9 => doubled - 9,
+                (_, doubled) => doubled,
+            };
+        }
+        !value.is_empty() && sum % 10 == 0
+    }
+
+    #[test]
+    fn regex_matches_whole_value() {
+        let percent = Type::new("percent", "100|[1-9]?[0-9]");
+        for raw_bytes in [false, true] {
+            assert!(check_value(b"100", &percent, raw_bytes));
+            assert!(check_value(b"5", &percent, raw_bytes));
+            assert!(!check_value(b"1000", &percent, raw_bytes));
+            assert!(!check_value(b"abc5", &percent, raw_bytes));
+        }
+    }
+
+    #[test]
+    fn match_with_closure() {
+        let card = Type::from_matcher("card", FnMatcher::new("luhn", luhn));
+        assert!(check_value(b"79927398713", &card, false));
+        assert!(!check_value(b"79927398710", &card, false));
+        assert!(check_value(b"79927398713", &card, true));
+        assert!(!check_value(b"\xff", &card, true));
+        assert_eq!("luhn", card.matcher.description());
+    }
+
+    #[test]
+    fn match_with_parser() {
+        let types = TypeList::from(vec![
+            Type::new("string", ".*"),
+            Type::from_matcher("ip", ParseMatcher::<std::net::IpAddr>::new()),
+            Type::from_matcher("float", ParseMatcher::<f64>::new()),
+        ]);
+        let column = vec!["127.0.0.1".to_owned(), "::1".to_owned()];
+        assert_eq!(
+            vec!["string", "ip"],
+            get_matching_types(&column, types.get_types_vec())
+                .iter()
+                .map(|t| t.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Type::from_matcher("float", ParseMatcher::<f64>::new()),
+            types.get_type("float").unwrap()
+        );
+    }
+
+    #[test]
+    fn match_dates_and_times() {
+        let date = TemporalMatcher::new(Temporal::Date, "%Y-%m-%d").unwrap();
+        assert!(date.is_match("2024-02-29"));
+        assert!(!date.is_match("2023-02-30"));
+        assert!(!date.is_match(" 2023-02-01"));
+        assert_eq!("date(%Y-%m-%d)", date.description());
+
+        let time = TemporalMatcher::new(Temporal::Time, "%H:%M").unwrap();
+        assert!(time.is_match("23:59"));
+        assert!(!time.is_match("25:61"));
+
+        let datetime = TemporalMatcher::new(Temporal::DateTime, "%Y-%m-%dT%H:%M:%S%.f%#z").unwrap();
+        assert!(datetime.is_match("2023-01-01T10:00:00Z"));
+        assert!(datetime.is_match("2023-01-01T10:00:00.250+02:00"));
+        assert!(!datetime.is_match("2023-01-01T10:00:00"));
+        assert!(!datetime.is_match("2023-01-01T10:00:00+2:00x"));
+
+        assert!(TemporalMatcher::new(Temporal::Date, "%Y-%Q").is_none());
+    }
+
+    #[test]
+    fn match_strict_dates() {
+        let date = TemporalMatcher::new(Temporal::Date, "%d.%m.%Y").unwrap();
+        assert!(date.is_match("1.2.23"));
+        let date = date.strict();
+        assert!(date.is_match("01.02.2023"));
+        assert!(!date.is_match("1.2.2023"));
+        assert!(!date.is_match("01.02.23"));
+
+        let datetime = TemporalMatcher::new(Temporal::DateTime, "%Y-%m-%dT%H:%M:%S%:z")
+            .unwrap()
+            .strict();
+        assert!(datetime.is_match("2023-01-01T10:00:00+02:00"));
+        assert!(!datetime.is_match("2023-01-01T10:00:00"));
+
+        let datetime = TemporalMatcher::new(Temporal::DateTime, "%Y-%m-%dT%H:%M:%S%.f%#z")
+            .unwrap()
+            .strict();
+        for value in &[
+            "2023-01-01T10:00:00Z",
+            "2023-01-01T10:00:00.5+02",
+            "2023-01-01T10:00:00.000-0230",
+            "2023-01-01T10:00:00.123456789+02:00",
+        ] {
+            assert!(datetime.is_match(value), "{}", value);
+        }
+        assert!(!datetime.is_match("2023-1-01T10:00:00Z"));
+        assert!(!datetime.is_match("2023-01-01T10:00:0Z"));
+
+        let time = TemporalMatcher::new(Temporal::Time, "%H:%M:%S%.f")
+            .unwrap()
+            .strict();
+        assert!(time.is_match("10:00:00"));
+        assert!(time.is_match("10:00:00.25"));
+        assert!(!time.is_match("1:2:3"));
+        assert!(!time.is_match("10:00:00."));
+    }
+
+    #[test]
+    fn match_numbers_with_separators() {
+        let de = NumberMatcher::new(',', Some('.')).unwrap();
+        for value in &["1.234,56", "-1.234.567", "1234,5", "0,5", ",5", "12"] {
+            assert!(de.is_match(value), "{}", value);
+        }
+        for value in &[
+            "1.23,4",
+            "1,234.56",
+            "1.2345",
+            "",
+            "-",
+            ",",
+            "1,2,3",
+            "12.345,6x",
+        ] {
+            assert!(!de.is_match(value), "{}", value);
+        }
+        let fr = NumberMatcher::new(',', Some(' ')).unwrap();
+        assert!(fr.is_match("1 234,56"));
+        assert!(fr.is_match("1\u{202f}234,56"));
+        assert!(!fr.is_match(" 234,56"));
+        let ch = NumberMatcher::new('.', Some('\'')).unwrap();
+        assert!(ch.is_match("1'234.56"));
+        assert_eq!("number(.')", ch.description());
+        assert!(NumberMatcher::new(',', Some(',')).is_none());
+        assert!(NumberMatcher::new('1', None).is_none());
+    }
+
+    #[test]
+    fn match_params() {
+        let wind = ParamMatcher::parse("int(0..360)").unwrap();
+        assert_eq!(Param::Int(Some(0), Some(360)), wind.param());
+        assert!(wind.is_match("360"));
+        assert!(wind.is_match("+0"));
+        assert!(!wind.is_match("540"));
+        assert!(!wind.is_match("-1"));
+        assert!(!wind.is_match("99999999999999999999"));
+        let latitude = ParamMatcher::parse("float(-90..90)").unwrap();
+        assert!(latitude.is_match("-90"));
+        assert!(latitude.is_match(".5"));
+        assert!(!latitude.is_match("90.01"));
+        assert!(!latitude.is_match("inf"));
+        let code = ParamMatcher::parse("varchar(3)").unwrap();
+        assert!(code.is_match("äöü"));
+        assert!(!code.is_match("abcd"));
+        let price = ParamMatcher::parse("decimal(4, 2)").unwrap();
+        assert_eq!("decimal(4,2)", price.description());
+        for value in &["12.34", "-0012.3", "1", ".05"] {
+            assert!(price.is_match(value), "{}", value);
+        }
+        for value in &["123.4", "1.234", "1e2", ""] {
+            assert!(!price.is_match(value), "{}", value);
+        }
+        assert_eq!(
+            "int(0..)",
+            ParamMatcher::parse("int(0..)").unwrap().description()
+        );
+        for definition in &[
+            "int(..)",
+            "int(5..1)",
+            "float(NaN..1)",
+            "decimal(2,3)",
+            "text(3)",
+            "int",
+        ] {
+            assert!(ParamMatcher::parse(definition).is_none(), "{}", definition);
+        }
+    }
+
+    #[test]
+    fn match_widths() {
+        assert!(Width::U8.is_match("255"));
+        assert!(Width::U8.is_match("-0"));
+        assert!(!Width::U8.is_match("256"));
+        assert!(!Width::U8.is_match("-1"));
+        assert!(Width::I8.is_match("-128"));
+        assert!(!Width::I8.is_match("128"));
+        assert!(Width::I32.is_match("+2147483647"));
+        assert!(!Width::I32.is_match("2147483648"));
+        assert!(Width::U64.is_match("18446744073709551615"));
+        assert!(!Width::U64.is_match("18446744073709551616"));
+        assert!(!Width::I64.is_match("1.0"));
+
+        for value in &["0.1", "-1.5", "16777216", "0.000", ".25", "1200."] {
+            assert!(Width::F32.is_match(value), "{}", value);
+        }
+        assert!(Width::F32.is_match(&format!("34028235{}", "0".repeat(31))));
+        assert!(Width::F32.is_match("-16777216"));
+        assert!(!Width::F32.is_match("16777217"));
+        assert!(Width::F32.is_match("16777218"));
+        assert!(Width::F64.is_match("9007199254740992"));
+        assert!(!Width::F64.is_match("9007199254740993"));
+        for value in &["0.123456789", "16777217", "1e5", "inf", "1.5."] {
+            assert!(!Width::F32.is_match(value), "{}", value);
+        }
+        assert!(!Width::F32.is_match(&"9".repeat(40)));
+        assert!(Width::F64.is_match("0.123456789"));
+        assert!(!Width::F64.is_match("3.14159265358979323846"));
+        assert_eq!(("12".to_owned(), -2), scientific("0.0120"));
+        assert_eq!(("12".to_owned(), -2), scientific("1.2e-2"));
+        assert_eq!(("12".to_owned(), 3), scientific("-1200."));
+        for value in &["0.0120", "-1200.", "1.5", "10.01", ".5", "0", "123"] {
+            let (digits, exponent) = scientific(value);
+            let expected = (if digits == "0" { 0 } else { digits.len() }, exponent);
+            assert_eq!(expected, significant_digits(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn regex_types_are_equal_by_pattern() {
+        assert_eq!(Type::new("int", r"\d+"), Type::new("int", r"\d+"));
+        assert_ne!(Type::new("int", r"\d+"), Type::new("int", r"\d*"));
+        assert_eq!(
+            Type::new("int", Native::Int.pattern()),
+            Type::from_native("int", Native::Int)
+        );
+    }
+}
@@ -1,103 +1,603 @@
+use super::cache::{self, ValueCache};
+use super::pool;
 use super::types;
-use super::vec;
-use super::Error;
-use std::thread;
+use super::{Column, ColumnStats, Error, Options, Outlier, TypeStats, MAX_DISTINCT, MAX_OUTLIERS};
+use std::collections::{BTreeMap, HashSet};
 
-pub fn get_matching_types(
-    csv: Vec<Vec<String>>,
-    type_list: types::TypeList,
+/// Narrows down the possible types of every column.
+///
+/// The records are checked in chunks on up to `max_threads` workers. Only the
+/// types which are still possible are kept for each column, so the memory needed
+/// grows with the number of columns and not with the number of rows. Null tokens
+/// are counted instead of being matched. Each value is checked against all
+/// remaining types at once with a `types::TypeSet`. The types of columns with nulls are
+/// nullable. The types of every column are sorted from the most to the least
+/// specific.
+///
+/// With `Options::cache_size` values which were seen before in a column are
+/// not checked again and the number of distinct values is returned.
+///
+/// With `Options::threshold` or `Options::stats` no type is removed while
+/// reading. Instead the matches of every type are counted, see `count_types`.
+pub fn get_matching_types<I>(
+    records: I,
+    type_list: &types::TypeList,
     max_threads: usize,
-) -> Result<Vec<Vec<types::Type>>, Error> {
-    let flipped_csv = vec::flip_vec(&csv);
-    let col_sets = vec::split_vec_equal(&flipped_csv, max_threads);
+    options: &Options,
+) -> Result<Vec<Column>, Error>
+where
+    I: Iterator<Item = csv::ByteRecord>,
+{
+    let set = types::TypeSet::new(type_list.get_types_vec(), options.raw_bytes);
+    let mut columns = match options.threshold {
+        Some(threshold) => count_types(records, type_list, &set, max_threads, options, threshold)?,
+        None if options.stats => count_types(records, type_list, &set, max_threads, options, 1.0)?,
+        None => {
+            let searches =
+                pool::process_chunks(records, max_threads, Search::default, |search, chunk| {
+                    search_types(search, &chunk, type_list, &set, options)
+                })?;
+            let (columns, caches): (Vec<_>, Vec<_>) = searches
+                .into_iter()
+                .map(|search| search.into_columns(type_list))
+                .unzip();
+            let mut columns = merge_columns(columns);
+            let caches = cache::merge(caches);
+            for (col_index, column) in columns.iter_mut().enumerate() {
+                type_list.sort_by_specificity(&mut column.types);
+                column.matched = vec![column.values - column.nulls; column.types.len()];
+                column.distinct = caches.get(col_index).and_then(ValueCache::distinct);
+            }
+            columns
+        }
+    };
 
-    let col_types = search_types(col_sets, &type_list)?;
+    for column in &mut columns {
+        let nullable = column.nulls > 0;
+        for type_def in &mut column.types {
+            type_def.nullable = nullable;
+        }
+    }
+    Ok(columns)
+}
 
-    Ok(col_types)
+/// Possible types of the columns seen by one worker.
+#[derive(Default)]
+struct Search {
+    /// Columns without types, they are only set by `into_columns`.
+    columns: Vec<Column>,
+    /// Positions in the type list of the remaining types of each column.
+    indices: Vec<Vec<usize>>,
+    /// Values which matched all remaining types, only with `Options::cache_size`.
+    caches: Vec<ValueCache<()>>,
+}
+
+impl Search {
+    /// Returns the columns with their remaining types and the caches.
+    fn into_columns(self, type_list: &types::TypeList) -> (Vec<Column>, Vec<ValueCache<()>>) {
+        let all_types = type_list.get_types_vec();
+        let columns = self
+            .columns
+            .into_iter()
+            .zip(self.indices)
+            .map(|(column, indices)| Column {
+                types: indices.into_iter().map(|i| all_types[i].clone()).collect(),
+                ..column
+            })
+            .collect();
+        (columns, self.caches)
+    }
 }
 
 fn search_types(
-    col_sets: Vec<Vec<Vec<String>>>,
+    search: &mut Search,
+    chunk: &pool::Chunk,
     type_list: &types::TypeList,
-) -> Result<Vec<Vec<types::Type>>, Error> {
-    let mut join_handlers = Vec::new();
-    for col_set in col_sets {
-        let type_list = type_list.get_types_vec().clone();
-        join_handlers.push(thread::spawn(move || {
-            let mut col_types = Vec::new();
-            for col in col_set {
-                col_types.push(types::get_matching_types(&col, &type_list))
+    set: &types::TypeSet,
+    options: &Options,
+) {
+    let all_types = type_list.get_types_vec();
+    for record in &chunk.records {
+        for (col_index, value) in record.iter().enumerate() {
+            if search.columns.len() <= col_index {
+                search.columns.push(Column {
+                    types: Vec::new(),
+                    matched: Vec::new(),
+                    values: 0,
+                    nulls: 0,
+                    outliers: Vec::new(),
+                    stats: None,
+                    distinct: None,
+                });
+                search.indices.push((0..all_types.len()).collect());
+                if let Some(cache_size) = options.cache_size {
+                    search.caches.push(ValueCache::new(cache_size));
+                }
             }
-            col_types
-        }));
-    }
-    let mut col_types = Vec::new();
-    for handler in join_handlers {
-        let col_type_cols = match handler.join() {
-            Ok(ctc) => ctc,
-            Err(_) => return Err(Error::Join),
-        };
-        for col_type_col in col_type_cols {
-            col_types.push(col_type_col);
+            let column = &mut search.columns[col_index];
+            column.values += 1;
+            if types::is_null(value, &options.null_tokens) {
+                column.nulls += 1;
+                continue;
+            }
+            let indices = &mut search.indices[col_index];
+            // Types are only removed, so the remaining ones matched every
+            // value in the cache.
+            match search.caches.get_mut(col_index) {
+                Some(cache) if cache.get(value).is_some() => {}
+                Some(cache) => {
+                    set.retain_matching(all_types, indices, value);
+                    cache.insert(value, ());
+                }
+                None => set.retain_matching(all_types, indices, value),
+            }
+        }
+    }
+}
+
+/// Keeps only the types which are possible in every worker's result and adds
+/// up the counts.
+fn merge_columns(results: Vec<Vec<Column>>) -> Vec<Column> {
+    let mut results = results.into_iter();
+    let mut merged = results.next().unwrap_or_default();
+    for columns in results {
+        for (col_index, col) in columns.into_iter().enumerate() {
+            match merged.get_mut(col_index) {
+                Some(merged_col) => {
+                    merged_col
+                        .types
+                        .retain(|t| col.types.iter().any(|c| c.name == t.name));
+                    merged_col.values += col.values;
+                    merged_col.nulls += col.nulls;
+                }
+                None => merged.push(col),
+            }
+        }
+    }
+    merged
+}
+
+/// Matches of every type of the type list in one column.
+#[derive(Default)]
+struct Counts {
+    values: u64,
+    nulls: u64,
+    /// Number of values which matched each type of the type list.
+    matched: Vec<u64>,
+    /// The first values which did not match each type of the type list.
+    failed: Vec<Vec<Outlier>>,
+    // Only counted with `Options::stats`.
+    empties: u64,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    distinct: HashSet<Vec<u8>>,
+    distinct_overflow: bool,
+    /// Matches of every type for values seen before, with `Options::cache_size`.
+    cache: Option<ValueCache<Vec<bool>>>,
+}
+
+impl Counts {
+    fn add_stats(&mut self, value: &[u8]) {
+        let length = std::str::from_utf8(value).map_or(value.len(), |value| value.chars().count());
+        self.min_length = Some(self.min_length.map_or(length, |min| min.min(length)));
+        self.max_length = Some(self.max_length.map_or(length, |max| max.max(length)));
+        self.add_distinct(value);
+    }
+
+    fn add_distinct(&mut self, value: &[u8]) {
+        if self.distinct.len() < MAX_DISTINCT {
+            self.distinct.insert(value.to_vec());
+        } else if !self.distinct.contains(value) {
+            self.distinct_overflow = true;
         }
     }
-    Ok(col_types)
+}
+
+/// Counts how many values of each column match each type.
+///
+/// Types which matched at least `threshold` of the values which are not null
+/// are kept. The values which did not match one of them are returned as
+/// outliers, at most `MAX_OUTLIERS` per column. With `Options::stats` the
+/// statistics of every column are collected as well.
+fn count_types<I>(
+    records: I,
+    type_list: &types::TypeList,
+    set: &types::TypeSet,
+    max_threads: usize,
+    options: &Options,
+    threshold: f64,
+) -> Result<Vec<Column>, Error>
+where
+    I: Iterator<Item = csv::ByteRecord>,
+{
+    let type_count = type_list.get_types_vec().len();
+    let results = pool::process_chunks(records, max_threads, Vec::new, |columns, chunk| {
+        for (row, record) in chunk.rows() {
+            for (col_index, value) in record.iter().enumerate() {
+                if columns.len() <= col_index {
+                    columns.push(Counts {
+                        matched: vec![0; type_count],
+                        failed: vec![Vec::new(); type_count],
+                        cache: options.cache_size.map(ValueCache::new),
+                        ..Counts::default()
+                    });
+                }
+                let counts: &mut Counts = &mut columns[col_index];
+                counts.values += 1;
+                if options.stats && value.is_empty() {
+                    counts.empties += 1;
+                }
+                if types::is_null(value, &options.null_tokens) {
+                    counts.nulls += 1;
+                    continue;
+                }
+                if options.stats {
+                    counts.add_stats(value);
+                }
+                let cached = counts.cache.as_ref().and_then(|cache| cache.get(value));
+                let checked = match cached {
+                    Some(_) => None,
+                    None => Some(set.check_all(type_list.get_types_vec(), value)),
+                };
+                for (type_index, &matched) in cached
+                    .or(checked.as_ref())
+                    .into_iter()
+                    .flatten()
+                    .enumerate()
+                {
+                    if matched {
+                        counts.matched[type_index] += 1;
+                    } else if counts.failed[type_index].len() < MAX_OUTLIERS {
+                        counts.failed[type_index].push(Outlier::new(row, record, col_index, value));
+                    }
+                }
+                if let (Some(cache), Some(checked)) = (counts.cache.as_mut(), checked) {
+                    cache.insert(value, checked);
+                }
+            }
+        }
+    })?;
+
+    let columns = merge_counts(results)
+        .into_iter()
+        .map(|counts| column_from_counts(counts, type_list, threshold, options.stats))
+        .collect();
+    Ok(columns)
+}
+
+/// Adds up the counts of all workers and keeps the first failed values.
+fn merge_counts(results: Vec<Vec<Counts>>) -> Vec<Counts> {
+    let mut results = results.into_iter();
+    let mut merged = results.next().unwrap_or_default();
+    for columns in results {
+        for (col_index, col) in columns.into_iter().enumerate() {
+            let merged_col = match merged.get_mut(col_index) {
+                Some(merged_col) => merged_col,
+                None => {
+                    merged.push(col);
+                    continue;
+                }
+            };
+            merged_col.values += col.values;
+            merged_col.nulls += col.nulls;
+            merged_col.empties += col.empties;
+            merged_col.min_length = match (merged_col.min_length, col.min_length) {
+                (Some(merged), Some(min)) => Some(merged.min(min)),
+                (merged, min) => merged.or(min),
+            };
+            merged_col.max_length = merged_col.max_length.max(col.max_length);
+            merged_col.distinct_overflow |= col.distinct_overflow;
+            for value in &col.distinct {
+                merged_col.add_distinct(value);
+            }
+            if let (Some(merged_cache), Some(cache)) = (merged_col.cache.as_mut(), col.cache) {
+                merged_cache.merge(cache);
+            }
+            for (type_index, matched) in col.matched.into_iter().enumerate() {
+                merged_col.matched[type_index] += matched;
+            }
+            for (type_index, failed) in col.failed.into_iter().enumerate() {
+                merged_col.failed[type_index].extend(failed);
+            }
+        }
+    }
+    for col in &mut merged {
+        for failed in &mut col.failed {
+            failed.sort_by_key(|outlier| outlier.row);
+            failed.truncate(MAX_OUTLIERS);
+        }
+    }
+    merged
+}
+
+fn column_from_counts(
+    counts: Counts,
+    type_list: &types::TypeList,
+    threshold: f64,
+    stats: bool,
+) -> Column {
+    let all_types = type_list.get_types_vec();
+    let checked = counts.values - counts.nulls;
+    let kept = (0..all_types.len())
+        .filter(|&i| checked == 0 || counts.matched[i] as f64 / checked as f64 >= threshold)
+        .collect::<Vec<_>>();
+
+    let mut types = kept
+        .iter()
+        .map(|&i| all_types[i].clone())
+        .collect::<Vec<_>>();
+    type_list.sort_by_specificity(&mut types);
+    let index_of = |name: &str| all_types.iter().position(|t| t.name == name).unwrap();
+    let matched = types
+        .iter()
+        .map(|t| counts.matched[index_of(&t.name)])
+        .collect();
+
+    let mut outliers = BTreeMap::new();
+    for type_def in &types {
+        for failed in &counts.failed[index_of(&type_def.name)] {
+            outliers
+                .entry(failed.row)
+                .or_insert_with(|| failed.clone())
+                .types
+                .push(type_def.name.clone());
+        }
+    }
+
+    let stats = if stats {
+        let types = all_types
+            .iter()
+            .enumerate()
+            .map(|(i, type_def)| TypeStats {
+                name: type_def.name.clone(),
+                matched: counts.matched[i],
+                first_failure: counts.failed[i].first().cloned(),
+            })
+            .collect();
+        Some(ColumnStats {
+            types,
+            empties: counts.empties,
+            distinct: counts.distinct.len() as u64,
+            distinct_overflow: counts.distinct_overflow,
+            min_length: counts.min_length,
+            max_length: counts.max_length,
+        })
+    } else {
+        None
+    };
+
+    Column {
+        types,
+        matched,
+        values: counts.values,
+        nulls: counts.nulls,
+        outliers: outliers.into_values().take(MAX_OUTLIERS).collect(),
+        stats,
+        distinct: counts.cache.as_ref().and_then(ValueCache::distinct),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn names(columns: Vec<Column>) -> Vec<Vec<String>> {
+        columns
+            .into_iter()
+            .map(|col| {
+                col.types
+                    .iter()
+                    .map(|ty| ty.to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn column(types: Vec<types::Type>, values: u64, nulls: u64) -> Column {
+        Column {
+            types,
+            matched: Vec::new(),
+            values,
+            nulls,
+            outliers: Vec::new(),
+            stats: None,
+            distinct: None,
+        }
+    }
 
     #[test]
     fn match_only_strings() {
         let types = vec![
-            super::types::Type::new("str", ".*"),
-            super::types::Type::new("num", r"\d*"),
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d*"),
         ];
-        let csv = vec![vec![
-            String::from("W"),
-            String::from("r"),
-            String::from("asd"),
-        ]];
-        let result =
-            super::get_matching_types(csv, super::types::TypeList::from(types), 4).map(|r| {
-                r.into_iter()
-                    .map(|col| col.into_iter().map(|ty| ty.name).collect::<Vec<_>>())
-                    .collect::<Vec<_>>()
-            });
+        let csv = vec![csv::ByteRecord::from(vec!["W", "r", "asd"])];
+        let result = get_matching_types(
+            csv.into_iter(),
+            &types::TypeList::from(types),
+            4,
+            &Options::default(),
+        )
+        .unwrap();
         assert_eq!(
-            Ok(vec![
+            vec![
                 vec!["str".to_owned()],
                 vec!["str".to_owned()],
                 vec!["str".to_owned()]
-            ]),
-            result
+            ],
+            names(result)
         );
     }
 
     #[test]
     fn match_multiple_types() {
         let types = vec![
-            super::types::Type::new("str", ".*"),
-            super::types::Type::new("num", r"\d*"),
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d*"),
         ];
-        let csv = vec![vec![
-            String::from("W"),
-            String::from("r"),
-            String::from("3"),
-        ]];
-        let result =
-            super::get_matching_types(csv, super::types::TypeList::from(types), 4).map(|r| {
-                r.into_iter()
-                    .map(|col| col.into_iter().map(|ty| ty.name).collect::<Vec<_>>())
-                    .collect::<Vec<_>>()
-            });
+        let csv = vec![csv::ByteRecord::from(vec!["W", "r", "3"])];
+        let result = get_matching_types(
+            csv.into_iter(),
+            &types::TypeList::from(types),
+            4,
+            &Options::default(),
+        )
+        .unwrap();
         assert_eq!(
-            Ok(vec![
+            vec![
                 vec!["str".to_owned()],
                 vec!["str".to_owned()],
-                vec!["str".to_owned(), "num".to_owned()]
-            ]),
-            result
+                vec!["num".to_owned(), "str".to_owned()]
+            ],
+            names(result)
         );
     }
+
+    #[test]
+    fn match_over_multiple_records() {
+        let types = vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d*"),
+        ];
+        let csv = vec![
+            csv::ByteRecord::from(vec!["1", "2"]),
+            csv::ByteRecord::from(vec!["3", "x"]),
+        ];
+        let result = get_matching_types(
+            csv.into_iter(),
+            &types::TypeList::from(types),
+            4,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                vec!["num".to_owned(), "str".to_owned()],
+                vec!["str".to_owned()]
+            ],
+            names(result)
+        );
+    }
+
+    #[test]
+    fn match_nullable_columns() {
+        let types = vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d+"),
+        ];
+        let csv = vec![
+            csv::ByteRecord::from(vec!["1", "NA"]),
+            csv::ByteRecord::from(vec!["", "NA"]),
+            csv::ByteRecord::from(vec!["3", "x"]),
+        ];
+        let options = Options {
+            null_tokens: vec![String::new(), String::from("NA")],
+            ..Options::default()
+        };
+        let result =
+            get_matching_types(csv.into_iter(), &types::TypeList::from(types), 2, &options)
+                .unwrap();
+        assert_eq!((3, 1), (result[0].values, result[0].nulls));
+        assert_eq!((3, 2), (result[1].values, result[1].nulls));
+        assert_eq!(
+            vec![
+                vec!["num?".to_owned(), "str?".to_owned()],
+                vec!["str?".to_owned()]
+            ],
+            names(result)
+        );
+    }
+
+    #[test]
+    fn merge_columns_intersects_columns() {
+        let str_type = types::Type::new("str", ".*");
+        let num_type = types::Type::new("num", r"\d*");
+        let merged = merge_columns(vec![
+            vec![column(vec![str_type.clone(), num_type.clone()], 2, 1)],
+            vec![],
+            vec![
+                column(vec![str_type.clone()], 3, 0),
+                column(vec![num_type.clone()], 1, 1),
+            ],
+        ]);
+        assert_eq!((5, 1), (merged[0].values, merged[0].nulls));
+        assert_eq!(
+            vec![vec!["str".to_owned()], vec!["num".to_owned()]],
+            names(merged)
+        );
+    }
+
+    #[test]
+    fn count_types_keeps_first_outliers() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d+"),
+        ]);
+        let rows = pool::CHUNK_SIZE * 3;
+        let csv = (0..rows).map(|row| {
+            let value = if row % 20 == 0 {
+                "x".to_owned()
+            } else {
+                row.to_string()
+            };
+            csv::ByteRecord::from(vec![value])
+        });
+        let options = Options {
+            threshold: Some(0.9),
+            ..Options::default()
+        };
+        let result = get_matching_types(csv, &types, 3, &options).unwrap();
+        assert_eq!(vec!["num", "str"], names(result.clone())[0]);
+        assert_eq!(
+            vec![(rows - rows.div_ceil(20)) as u64, rows as u64],
+            result[0].matched
+        );
+        assert_eq!(
+            (0..MAX_OUTLIERS).map(|i| i * 20).collect::<Vec<_>>(),
+            result[0].outliers.iter().map(|o| o.row).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn cache_gives_same_types_and_counts_distinct_values() {
+        let types = types::TypeList::from(vec![
+            types::Type::new("str", ".*"),
+            types::Type::new("num", r"\d+"),
+        ]);
+        let rows = pool::CHUNK_SIZE * 3;
+        let csv = (0..rows)
+            .map(|row| {
+                let status = if row % 500 == 7 { "x" } else { "200" };
+                csv::ByteRecord::from(vec![status.to_owned(), (row % 13).to_string()])
+            })
+            .collect::<Vec<_>>();
+        for threshold in [None, Some(0.9)] {
+            let without_cache = Options {
+                threshold,
+                ..Options::default()
+            };
+            let expected =
+                get_matching_types(csv.clone().into_iter(), &types, 3, &without_cache).unwrap();
+            let options = Options {
+                cache_size: Some(13),
+                ..without_cache.clone()
+            };
+            let result = get_matching_types(csv.clone().into_iter(), &types, 3, &options).unwrap();
+            assert_eq!(names(expected.clone()), names(result.clone()));
+            assert_eq!(expected[0].matched, result[0].matched);
+            assert_eq!(expected[0].outliers, result[0].outliers);
+            assert_eq!(
+                vec![Some(2), Some(13)],
+                result.iter().map(|c| c.distinct).collect::<Vec<_>>()
+            );
+
+            let options = Options {
+                cache_size: Some(12),
+                ..without_cache
+            };
+            let result = get_matching_types(csv.clone().into_iter(), &types, 3, &options).unwrap();
+            assert_eq!(names(expected), names(result.clone()));
+            assert_eq!(None, result[1].distinct);
+        }
+    }
 }
@@ -0,0 +1,158 @@
+use super::Error;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+/// Number of records handed to a worker at once.
+pub const CHUNK_SIZE: usize = 1024;
+
+/// Consecutive records together with the index of the first one.
+pub struct Chunk {
+    pub first_row: usize,
+    pub records: Vec<csv::ByteRecord>,
+}
+
+impl Chunk {
+    /// Iterates over the records together with their row index.
+    pub fn rows(&self) -> impl Iterator<Item = (usize, &csv::ByteRecord)> {
+        let first_row = self.first_row;
+        self.records
+            .iter()
+            .enumerate()
+            .map(move |(offset, record)| (first_row + offset, record))
+    }
+}
+
+/// Splits `records` into chunks and checks them on a pool of `threads` workers.
+///
+/// Each worker creates its own state with `init` and updates it with `work` for
+/// every chunk it receives. The chunks are read while the workers are busy and
+/// only a few of them are queued at any time. Once all records are processed
+/// the states of all workers are returned so they can be merged. If a worker
+/// panics `Error::Join` is returned, reading stops once no worker is left.
+pub fn process_chunks<I, S, N, W>(
+    records: I,
+    threads: usize,
+    init: N,
+    work: W,
+) -> Result<Vec<S>, Error>
+where
+    I: Iterator<Item = csv::ByteRecord>,
+    S: Send,
+    N: Fn() -> S + Sync,
+    W: Fn(&mut S, Chunk) + Sync,
+{
+    if threads <= 1 {
+        let mut state = init();
+        for chunk in chunks(records) {
+            work(&mut state, chunk);
+        }
+        return Ok(vec![state]);
+    }
+
+    let (sender, receiver) = mpsc::sync_channel::<Chunk>(threads * 2);
+    // Every worker holds the receiver, so sending fails once all are gone.
+    let receiver = Arc::new(Mutex::new(receiver));
+    let (init, work) = (&init, &work);
+
+    thread::scope(|scope| {
+        let mut join_handlers = Vec::new();
+        for _ in 0..threads {
+            let receiver = Arc::clone(&receiver);
+            join_handlers.push(scope.spawn(move || {
+                let mut state = init();
+                loop {
+                    let chunk = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => break,
+                    };
+                    match chunk {
+                        Ok(chunk) => work(&mut state, chunk),
+                        Err(_) => break,
+                    }
+                }
+                state
+            }));
+        }
+        drop(receiver);
+
+        for chunk in chunks(records) {
+            if sender.send(chunk).is_err() {
+                break;
+            }
+        }
+        drop(sender);
+
+        // Every worker is joined, otherwise the scope panics for the others.
+        let states = join_handlers
+            .into_iter()
+            .map(|handler| handler.join())
+            .collect::<Vec<_>>();
+        states
+            .into_iter()
+            .map(|state| state.map_err(|_| Error::Join))
+            .collect()
+    })
+}
+
+fn chunks<I: Iterator<Item = csv::ByteRecord>>(records: I) -> impl Iterator<Item = Chunk> {
+    let mut records = records.peekable();
+    let mut first_row = 0;
+    std::iter::from_fn(move || {
+        records.peek()?;
+        let chunk = Chunk {
+            first_row,
+            records: records.by_ref().take(CHUNK_SIZE).collect(),
+        };
+        first_row += chunk.records.len();
+        Some(chunk)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(count: usize) -> impl Iterator<Item = csv::ByteRecord> {
+        (0..count).map(|i| csv::ByteRecord::from(vec![i.to_string()]))
+    }
+
+    #[test]
+    fn chunks_keep_row_indices() {
+        let chunks = chunks(records(CHUNK_SIZE + 1)).collect::<Vec<_>>();
+        assert_eq!(2, chunks.len());
+        assert_eq!(CHUNK_SIZE, chunks[0].records.len());
+        assert_eq!(CHUNK_SIZE, chunks[1].first_row);
+        assert_eq!(
+            Some((CHUNK_SIZE, &chunks[1].records[0])),
+            chunks[1].rows().next()
+        );
+    }
+
+    #[test]
+    fn process_chunks_sees_every_row_once() {
+        for threads in 1..5 {
+            let states = process_chunks(
+                records(CHUNK_SIZE * 3 + 7),
+                threads,
+                Vec::new,
+                |rows, chunk| rows.extend(chunk.rows().map(|(row, _)| row)),
+            )
+            .unwrap();
+            assert_eq!(threads, states.len());
+            let mut rows = states.into_iter().flatten().collect::<Vec<_>>();
+            rows.sort_unstable();
+            assert_eq!((0..CHUNK_SIZE * 3 + 7).collect::<Vec<_>>(), rows);
+        }
+    }
+
+    #[test]
+    fn process_chunks_stops_when_all_workers_panicked() {
+        let result = process_chunks(
+            records(CHUNK_SIZE * 20),
+            2,
+            || (),
+            |_, _| panic!("worker failed"),
+        );
+        assert_eq!(Some(Error::Join), result.err());
+    }
+}
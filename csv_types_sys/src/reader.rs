@@ -0,0 +1,335 @@
+use super::encoding;
+use super::{CsvInput, Error, ErrorPolicy, MalformedRecord, Options, RaggedRecord};
+use std::io;
+
+/// Creates a reader for the input. Readers which are already configured are
+/// returned unchanged. `CsvInput::Read` is decoded to UTF-8 first.
+pub fn csv_reader<'a>(
+    csv_input: CsvInput<'a>,
+    options: &Options,
+) -> Result<csv::Reader<Box<dyn io::Read + 'a>>, Error> {
+    let input: Box<dyn io::Read + 'a> = match csv_input {
+        CsvInput::Csv(csv) => Box::new(csv.as_bytes()),
+        CsvInput::Read(read) => {
+            encoding::decode(read, options.encoding)
+                .map_err(|err| Error::Read(err.to_string()))?
+                .0
+        }
+        CsvInput::Reader(reader) => return Ok(reader),
+    };
+    Ok(options
+        .dialect
+        .reader_builder()
+        .flexible(options.flexible)
+        .from_reader(input))
+}
+
+/// Iterates over all records which could be parsed.
+///
+/// Records which can not be parsed are handled according to the policy. With
+/// `ErrorPolicy::Fail` the iteration stops at the first one and the error is
+/// kept in `error`, otherwise they are skipped and kept in `malformed`.
+///
+/// If `width` is set, records with a different number of fields are skipped
+/// and kept in `ragged`. This is used together with a flexible reader.
+///
+/// Fields have to be valid UTF-8 unless `raw_bytes` is set.
+pub struct Records<'r, R> {
+    records: csv::ByteRecordsIter<'r, R>,
+    policy: ErrorPolicy,
+    width: Option<Width>,
+    raw_bytes: bool,
+    pub malformed: Vec<MalformedRecord>,
+    pub ragged: Vec<RaggedRecord>,
+    pub error: Option<Error>,
+}
+
+/// Number of fields a record must have.
+#[derive(Clone, Copy)]
+pub enum Width {
+    /// Use the width of the first record.
+    First,
+    Fields(usize),
+}
+
+impl<'r, R: io::Read> Records<'r, R> {
+    pub fn new(reader: &'r mut csv::Reader<R>, policy: ErrorPolicy) -> Self {
+        Self {
+            records: reader.byte_records(),
+            policy,
+            width: None,
+            raw_bytes: false,
+            malformed: Vec::new(),
+            ragged: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Skips records which do not have the given number of fields.
+    pub fn with_width(mut self, width: Width) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Passes fields on without checking that they are valid UTF-8.
+    pub fn with_raw_bytes(mut self, raw_bytes: bool) -> Self {
+        self.raw_bytes = raw_bytes;
+        self
+    }
+
+    /// Returns the skipped records or the error which stopped the iteration.
+    pub fn finish(self) -> Result<(Vec<MalformedRecord>, Vec<RaggedRecord>), Error> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok((self.malformed, self.ragged)),
+        }
+    }
+
+    /// Returns `false` and keeps the record if it does not have the expected width.
+    fn check_width(&mut self, record: &csv::ByteRecord) -> bool {
+        let expected = match self.width {
+            None => return true,
+            Some(Width::First) => {
+                self.width = Some(Width::Fields(record.len()));
+                return true;
+            }
+            Some(Width::Fields(expected)) => expected,
+        };
+        if record.len() == expected {
+            return true;
+        }
+        if let Some(position) = record.position() {
+            self.ragged.push(RaggedRecord {
+                record: position.record() + 1,
+                line: position.line(),
+                byte: position.byte(),
+                expected,
+                found: record.len(),
+            });
+        }
+        false
+    }
+
+    /// Returns the record as malformed if a field is not valid UTF-8.
+    fn check_utf8(&self, record: &csv::ByteRecord) -> Option<MalformedRecord> {
+        if self.raw_bytes {
+            return None;
+        }
+        let field = record
+            .iter()
+            .position(|field| std::str::from_utf8(field).is_err())?;
+        let position = record.position()?;
+        Some(MalformedRecord {
+            record: position.record() + 1,
+            line: position.line(),
+            byte: position.byte(),
+            kind: format!("invalid UTF-8 in field {}", field + 1),
+        })
+    }
+}
+
+impl<R: io::Read> Iterator for Records<'_, R> {
+    type Item = csv::ByteRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        loop {
+            let malformed = match self.records.next()? {
+                Ok(record) => match self.check_utf8(&record) {
+                    None if self.check_width(&record) => return Some(record),
+                    None => continue,
+                    Some(malformed) => malformed,
+                },
+                Err(err) => match MalformedRecord::from_csv_error(&err) {
+                    Some(malformed) => malformed,
+                    None => {
+                        self.error = Some(Error::Read(err.to_string()));
+                        return None;
+                    }
+                },
+            };
+            match self.policy {
+                ErrorPolicy::Fail => {
+                    self.error = Some(Error::Malformed(malformed));
+                    return None;
+                }
+                ErrorPolicy::Skip | ErrorPolicy::Collect => self.malformed.push(malformed),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Dialect;
+
+    fn flexible() -> Options {
+        Options {
+            flexible: true,
+            ..Options::default()
+        }
+    }
+
+    fn reader<'a>(
+        csv_input: CsvInput<'a>,
+        options: &Options,
+    ) -> csv::Reader<Box<dyn io::Read + 'a>> {
+        csv_reader(csv_input, options).unwrap()
+    }
+
+    fn collect(csv: &str) -> Vec<Vec<String>> {
+        let mut reader = reader(CsvInput::Csv(csv), &Options::default());
+        Records::new(&mut reader, ErrorPolicy::Fail)
+            .map(|record| {
+                record
+                    .iter()
+                    .map(|field| String::from_utf8(field.to_vec()).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_csv_one_line() {
+        let expected = vec![vec!["v1".to_owned(), "v2".to_owned(), "v3".to_owned()]];
+        assert_eq!(expected, collect("v1,v2,v3"));
+    }
+
+    #[test]
+    fn parse_csv_multiple_lines() {
+        let expected = vec![
+            vec!["v4".to_owned(), "v34".to_owned(), "v7".to_owned()],
+            vec!["v1".to_owned(), "v2".to_owned(), "v3".to_owned()],
+        ];
+        assert_eq!(expected, collect("v4,v34,v7\nv1,v2,v3"));
+    }
+
+    #[test]
+    fn parse_csv_from_read() {
+        let mut reader = reader(
+            CsvInput::Read(Box::new(&b"v1,v2\nv3,v4"[..])),
+            &Options::default(),
+        );
+        let records = Records::new(&mut reader, ErrorPolicy::Fail).collect::<Vec<_>>();
+        assert_eq!(2, records.len());
+        assert_eq!(&records[1], vec!["v3", "v4"]);
+    }
+
+    #[test]
+    fn parse_csv_with_encoding() {
+        let options = Options {
+            encoding: encoding::Encoding::for_label("latin1").unwrap(),
+            ..Options::default()
+        };
+        let mut reader = reader(
+            CsvInput::Read(Box::new(&b"M\xfcnchen,K\xf6ln"[..])),
+            &options,
+        );
+        let records = Records::new(&mut reader, ErrorPolicy::Fail).collect::<Vec<_>>();
+        assert_eq!(&records[0], vec!["München", "Köln"]);
+    }
+
+    #[test]
+    fn records_with_raw_bytes() {
+        let mut reader = reader(
+            CsvInput::Read(Box::new(&b"a,\xff"[..])),
+            &Options::default(),
+        );
+        let mut records = Records::new(&mut reader, ErrorPolicy::Fail).with_raw_bytes(true);
+        assert_eq!(&records.next().unwrap()[1], b"\xff");
+        assert!(records.finish().is_ok());
+    }
+
+    #[test]
+    fn parse_csv_with_dialect() {
+        let options = Options {
+            dialect: Dialect {
+                delimiter: b'\t',
+                ..Dialect::default()
+            },
+            ..Options::default()
+        };
+        let mut reader = reader(CsvInput::Csv("v1\tv,2"), &options);
+        let records = Records::new(&mut reader, ErrorPolicy::Fail).collect::<Vec<_>>();
+        assert_eq!(&records[0], vec!["v1", "v,2"]);
+    }
+
+    #[test]
+    fn records_collect_malformed_records() {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(&b"a,b\nc\nd,e\n\xff,f"[..]);
+        let mut records = Records::new(&mut reader, ErrorPolicy::Collect);
+        let valid = records.by_ref().collect::<Vec<_>>();
+        assert_eq!(2, valid.len());
+        assert_eq!(&valid[1], vec!["d", "e"]);
+
+        let (malformed, _) = records.finish().unwrap();
+        assert_eq!(2, malformed.len());
+        assert_eq!(
+            (2, 2, 4),
+            (malformed[0].record, malformed[0].line, malformed[0].byte)
+        );
+        assert_eq!("expected 2 fields, found 1", malformed[0].kind);
+        assert_eq!(
+            (4, 4, 10),
+            (malformed[1].record, malformed[1].line, malformed[1].byte)
+        );
+        assert_eq!("invalid UTF-8 in field 1", malformed[1].kind);
+    }
+
+    #[test]
+    fn records_skip_ragged_records() {
+        let mut reader = reader(CsvInput::Csv("a,b\nc\nd,e,f\ng,h"), &flexible());
+        let mut records = Records::new(&mut reader, ErrorPolicy::Fail).with_width(Width::First);
+        let valid = records.by_ref().collect::<Vec<_>>();
+        assert_eq!(2, valid.len());
+        assert_eq!(&valid[1], vec!["g", "h"]);
+
+        let (malformed, ragged) = records.finish().unwrap();
+        assert!(malformed.is_empty());
+        assert_eq!(
+            vec![
+                RaggedRecord {
+                    record: 2,
+                    line: 2,
+                    byte: 4,
+                    expected: 2,
+                    found: 1
+                },
+                RaggedRecord {
+                    record: 3,
+                    line: 3,
+                    byte: 6,
+                    expected: 2,
+                    found: 3
+                }
+            ],
+            ragged
+        );
+    }
+
+    #[test]
+    fn records_with_fixed_width() {
+        let mut reader = reader(CsvInput::Csv("a\nb,c"), &flexible());
+        let mut records = Records::new(&mut reader, ErrorPolicy::Fail).with_width(Width::Fields(2));
+        assert_eq!(1, records.by_ref().count());
+        assert_eq!(1, records.finish().unwrap().1.len());
+    }
+
+    #[test]
+    fn records_fail_on_malformed_record() {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(&b"a,b\nc\nd,e"[..]);
+        let mut records = Records::new(&mut reader, ErrorPolicy::Fail);
+        assert_eq!(1, records.by_ref().count());
+        match records.finish() {
+            Err(Error::Malformed(malformed)) => assert_eq!(2, malformed.record),
+            _ => panic!("expected a malformed record"),
+        }
+    }
+}
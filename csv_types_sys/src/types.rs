@@ -1,44 +1,262 @@
-use regex::Regex;
+use super::matcher::{
+    self, NumberMatcher, Param, ParamMatcher, RegexMatcher, TemporalMatcher, TypeMatcher, Width,
+};
+use super::native::Native;
+use super::Error;
+use regex::bytes;
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
 
 type TypesMap = HashMap<String, Type>;
 type TypeVec = Vec<Type>;
 
 #[derive(Debug, Clone)]
 pub struct Type {
-    pub pattern: Regex,
+    /// Decides which values have the type.
+    pub matcher: Arc<dyn TypeMatcher>,
     pub name: String,
+    /// Null tokens are accepted as well. Shown as a `?` after the name.
+    pub nullable: bool,
+    /// Name of a less specific type which matches every value of this type,
+    /// like `float` for `int`.
+    pub parent: Option<String>,
 }
 
 impl Type {
+    /// Creates a type whose values match the regex `pattern` as a whole.
     pub fn new(name: &str, pattern: &str) -> Self {
+        Self::from_matcher(name, RegexMatcher::new(pattern))
+    }
+
+    /// Creates a type which is matched without a regex where possible, see
+    /// `RegexMatcher::from_native`.
+    pub fn from_native(name: &str, native: Native) -> Self {
+        Self::from_matcher(name, RegexMatcher::from_native(native))
+    }
+
+    pub fn from_matcher<M: TypeMatcher + 'static>(name: &str, matcher: M) -> Self {
         Self {
+            matcher: Arc::new(matcher),
             name: name.to_owned(),
-            pattern: Regex::new(&format!("^{}$", pattern)).unwrap(),
+            nullable: false,
+            parent: None,
+        }
+    }
+
+    pub fn with_parent(mut self, parent: &str) -> Self {
+        self.parent = Some(parent.to_owned());
+        self
+    }
+
+    pub fn with_nullable(mut self, nullable: bool) -> Self {
+        self.nullable = nullable;
+        self
+    }
+}
+
+impl PartialEq for Type {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.matcher.description() == other.matcher.description()
+            && self.nullable == other.nullable
+            && self.parent == other.parent
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.nullable {
+            write!(f, "{}?", self.name)
+        } else {
+            write!(f, "{}", self.name)
         }
     }
 }
 
 pub fn get_matching_types(column: &[String], type_list: &[Type]) -> TypeVec {
     let mut type_list = type_list.to_owned();
-    type_list.retain(|type_def| {
-        let reg = &type_def.pattern;
-        for value in column.iter() {
-            if !reg.is_match(value) {
-                return false;
-            }
-        }
-        true
-    });
+    for value in column.iter() {
+        retain_matching_types(&mut type_list, value.as_bytes(), false);
+    }
     type_list
 }
 
+/// Removes every type from `type_list` which does not match `value`.
+pub fn retain_matching_types(type_list: &mut TypeVec, value: &[u8], raw_bytes: bool) {
+    type_list.retain(|type_def| check_value(value, type_def, raw_bytes));
+}
+
+/// Smallest number of regex types for which `TypeSet` builds a set.
+const MIN_SET_SIZE: usize = 2;
+
+/// The regex types of a type list compiled into one `RegexSet`, so a value is
+/// scanned once for all of them. Other types are checked one by one. Matches
+/// like `check_value`.
+///
+/// Like `RegexMatcher` the set only uses Unicode for patterns which need it,
+/// a set with Unicode classes like `\w` is many times slower. Values which
+/// are not ASCII are therefore checked one by one unless raw bytes are matched.
+///
+/// The set is built once per type list. Types which have been ruled out for a
+/// column stay in it, only their results are not used.
+#[derive(Debug, Clone)]
+pub struct TypeSet {
+    set: Option<bytes::RegexSet>,
+    /// Position in the set of every type of the type list, `None` for types
+    /// which are not a regex.
+    positions: Vec<Option<usize>>,
+    raw_bytes: bool,
+}
+
+impl TypeSet {
+    pub fn new(types: &[Type], raw_bytes: bool) -> Self {
+        let mut patterns = Vec::new();
+        let positions = types
+            .iter()
+            .map(|type_def| {
+                let pattern = type_def.matcher.regex()?;
+                let ascii = format!("(?-u){}", pattern);
+                match bytes::Regex::new(&ascii) {
+                    Ok(_) => patterns.push(ascii),
+                    Err(_) => patterns.push(pattern.to_owned()),
+                }
+                Some(patterns.len() - 1)
+            })
+            .collect();
+        let set = if patterns.len() >= MIN_SET_SIZE {
+            Some(bytes::RegexSet::new(patterns).unwrap())
+        } else {
+            None
+        };
+        Self {
+            set,
+            positions,
+            raw_bytes,
+        }
+    }
+
+    /// Runs the set over the value. Returns `None` if there is no set or the
+    /// value has to be checked type by type.
+    pub fn matches(&self, value: &[u8]) -> Option<bytes::SetMatches> {
+        let set = self.set.as_ref()?;
+        if !self.raw_bytes && !value.is_ascii() {
+            return None;
+        }
+        Some(set.matches(value))
+    }
+
+    /// Checks the type at `index` of `types`, the type list the set was built
+    /// from, with the result of `matches` where possible.
+    pub fn is_match(
+        &self,
+        index: usize,
+        types: &[Type],
+        value: &[u8],
+        matches: Option<&bytes::SetMatches>,
+    ) -> bool {
+        match (matches, self.positions[index]) {
+            (Some(matches), Some(position)) => matches.matched(position),
+            _ => check_value(value, &types[index], self.raw_bytes),
+        }
+    }
+
+    /// Checks a single type. The set is not run for one type, its own regex
+    /// is faster.
+    pub fn check(&self, index: usize, types: &[Type], value: &[u8]) -> bool {
+        self.is_match(index, types, value, None)
+    }
+
+    /// Checks the value against every type of `types`.
+    pub fn check_all(&self, types: &[Type], value: &[u8]) -> Vec<bool> {
+        let matches = self.matches(value);
+        (0..types.len())
+            .map(|index| self.is_match(index, types, value, matches.as_ref()))
+            .collect()
+    }
+
+    /// Keeps the indices into `types` of the types which match the value. The
+    /// set is only run while at least two of them are in it, a single type is
+    /// faster with its own regex.
+    pub fn retain_matching(&self, types: &[Type], indices: &mut Vec<usize>, value: &[u8]) {
+        let in_set = indices
+            .iter()
+            .filter(|&&index| self.positions[index].is_some())
+            .count();
+        let matches = if in_set >= MIN_SET_SIZE {
+            self.matches(value)
+        } else {
+            None
+        };
+        indices.retain(|&index| self.is_match(index, types, value, matches.as_ref()));
+    }
+}
+
+/// Checks a field of a `csv::ByteRecord`. Unless `raw_bytes` is set the value
+/// has to be valid UTF-8 and is checked with `check_if_type_matches`.
+pub fn check_value(value: &[u8], type_def: &Type, raw_bytes: bool) -> bool {
+    if raw_bytes {
+        return check_if_bytes_match(value, type_def);
+    }
+    std::str::from_utf8(value).is_ok_and(|value| check_if_type_matches(value, type_def))
+}
+
 pub fn check_if_type_matches(value: &str, type_def: &Type) -> bool {
-    let reg = &type_def.pattern;
-    reg.is_match(value)
+    type_def.matcher.is_match(value)
+}
+
+/// Returns `true` if the value is one of the null tokens.
+pub fn is_null(value: &[u8], null_tokens: &[String]) -> bool {
+    null_tokens.iter().any(|token| token.as_bytes() == value)
+}
+
+/// Checks the raw bytes of a value, see `TypeMatcher::is_match_bytes`.
+pub fn check_if_bytes_match(value: &[u8], type_def: &Type) -> bool {
+    type_def.matcher.is_match_bytes(value)
+}
+
+/// A strict type for every format of `matcher::DATE_FORMATS`, named like
+/// `date(%d.%m.%Y)`. Added to a type list every format which fits all values of
+/// a column is kept, so `03/04/2021` keeps both `%d/%m/%Y` and `%m/%d/%Y` until
+/// another value like `13/04/2021` rules one of them out. Their parent is
+/// `string`.
+pub fn date_formats() -> TypeVec {
+    matcher::DATE_FORMATS
+        .iter()
+        .map(|&(temporal, format)| {
+            let matcher = TemporalMatcher::new(temporal, format).unwrap().strict();
+            let name = matcher.description().to_owned();
+            Type::from_matcher(&name, matcher).with_parent("string")
+        })
+        .collect()
+}
+
+/// A number type for every convention of `matcher::NUMBER_LOCALES`, named like
+/// `number(de)`. Like with `date_formats` the values of a column rule out the
+/// conventions which do not fit, `1,234` fits `en`, `de` and `fr`. Their parent
+/// is `string`.
+pub fn number_locales() -> TypeVec {
+    matcher::NUMBER_LOCALES
+        .iter()
+        .map(|&(locale, decimal, grouping)| {
+            let matcher = NumberMatcher::new(decimal, Some(grouping)).unwrap();
+            Type::from_matcher(&format!("number({})", locale), matcher).with_parent("string")
+        })
+        .collect()
 }
 
-#[derive(Debug, Default)]
+/// The types of `Width::ALL` with their parents, `u8` to `u64` and `i8` to
+/// `i64` end at `int`, `f32` and `f64` at `float`. Sorted by specificity the
+/// narrowest integer type which fits a column comes first. They are not part
+/// of the default types, but `TypeList::get_type` knows them by name.
+pub fn width_types() -> TypeVec {
+    Width::ALL
+        .iter()
+        .map(|&width| Type::from_matcher(width.name(), width).with_parent(width.parent()))
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct TypeList {
     map: TypesMap,
     list: TypeVec,
@@ -61,7 +279,7 @@ impl TypeList {
     }
 
     pub fn add_type(&mut self, type_config: Type) {
-        if self.map.get(&type_config.name).is_none() {
+        if !self.map.contains_key(&type_config.name) {
             self.list.push(type_config.clone());
         } else {
             self.list.retain(|t| t.name != type_config.name);
@@ -77,6 +295,82 @@ impl TypeList {
     pub fn get_types_map(&self) -> &TypesMap {
         &self.map
     }
+
+    /// Number of ancestors of a type. Types without a parent or with an
+    /// unknown parent have a specificity of 0, so do types in a parent cycle,
+    /// see `check_parents`.
+    pub fn specificity(&self, name: &str) -> usize {
+        self.ancestors(name).unwrap_or(0)
+    }
+
+    /// Returns `Error::ParentCycle` if a type is its own ancestor.
+    pub fn check_parents(&self) -> Result<(), Error> {
+        match self.list.iter().find(|t| self.ancestors(&t.name).is_none()) {
+            Some(type_def) => Err(Error::ParentCycle(type_def.name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of ancestors of a type, `None` if the parents lead to a cycle.
+    fn ancestors(&self, name: &str) -> Option<usize> {
+        let mut depth = 0;
+        let mut current = self.map.get(name);
+        while let Some(parent) = current.and_then(|t| t.parent.as_ref()) {
+            current = self.map.get(parent);
+            if current.is_none() {
+                break;
+            }
+            // A chain without a cycle visits every type at most once.
+            if depth == self.map.len() {
+                return None;
+            }
+            depth += 1;
+        }
+        Some(depth)
+    }
+
+    /// Sorts types from the most to the least specific. Types with the same
+    /// specificity are sorted by their position in the list, the last one
+    /// first, so types added later win over the default types.
+    pub fn sort_by_specificity(&self, types: &mut [Type]) {
+        let position = |name: &str| self.list.iter().position(|t| t.name == name);
+        types.sort_by_cached_key(|t| {
+            std::cmp::Reverse((self.specificity(&t.name), position(&t.name)))
+        });
+    }
+
+    /// Looks up a type by name. A `?` after the name returns a nullable type.
+    /// Names with parameters like `int(0..360)` which are not in the list give
+    /// a type checked by `ParamMatcher`, see `param_type`. Sized types like
+    /// `i32` which are not in the list are taken from `width_types`.
+    pub fn get_type(&self, name: &str) -> Option<Type> {
+        match name.strip_suffix('?') {
+            Some(name) => self.get_type(name).map(|t| t.with_nullable(true)),
+            None => self
+                .map
+                .get(name)
+                .cloned()
+                .or_else(|| self.param_type(name))
+                .or_else(|| width_types().into_iter().find(|t| t.name == name)),
+        }
+    }
+
+    /// Creates a type like `int(0..360)` or `decimal(10,2)`. Its parent is
+    /// `int`, `float` or `string` if the list has a type of that name.
+    pub fn param_type(&self, definition: &str) -> Option<Type> {
+        let matcher = ParamMatcher::parse(definition)?;
+        let parent = match matcher.param() {
+            Param::Int(..) => "int",
+            Param::Float(..) | Param::Decimal { .. } => "float",
+            Param::Varchar(_) => "string",
+        };
+        let type_def = Type::from_matcher(definition, matcher);
+        if self.map.contains_key(parent) {
+            Some(type_def.with_parent(parent))
+        } else {
+            Some(type_def)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -92,7 +386,13 @@ mod tests {
         let mut tl = TypeList::new();
         tl.add_type(Type::new("string", ".*"));
         let expected = vec!["string".to_owned()];
-        assert_eq!(expected, get_matching_types(&col, &tl.get_types_vec()).into_iter().map(|a| a.name.to_owned()).collect::<Vec<_>>());
+        assert_eq!(
+            expected,
+            get_matching_types(&col, tl.get_types_vec())
+                .into_iter()
+                .map(|a| a.name.to_owned())
+                .collect::<Vec<_>>()
+        );
     }
 
     #[test]
@@ -105,7 +405,13 @@ mod tests {
         tl.add_type(Type::new("string", ".*"));
         tl.add_type(Type::new("int", r"^\d*$"));
         let expected = vec!["string".to_owned(), "int".to_owned()];
-        assert_eq!(expected, get_matching_types(&col, &tl.get_types_vec()).into_iter().map(|a| a.name.to_owned()).collect::<Vec<_>>());
+        assert_eq!(
+            expected,
+            get_matching_types(&col, tl.get_types_vec())
+                .into_iter()
+                .map(|a| a.name.to_owned())
+                .collect::<Vec<_>>()
+        );
     }
 
     #[test]
@@ -118,6 +424,207 @@ mod tests {
         tl.add_type(Type::new("string", ".*"));
         tl.add_type(Type::new("int", r"^\d*$"));
         let expected = vec!["string".to_owned()];
-        assert_eq!(expected, get_matching_types(&col, &tl.get_types_vec()).into_iter().map(|a| a.name.to_owned()).collect::<Vec<_>>());
+        assert_eq!(
+            expected,
+            get_matching_types(&col, tl.get_types_vec())
+                .into_iter()
+                .map(|a| a.name.to_owned())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn match_raw_bytes() {
+        let type_def = Type::new("city", r"M\xfcnchen");
+        assert!(check_value(b"M\xfcnchen", &type_def, true));
+        assert!(check_value("München".as_bytes(), &type_def, false));
+        assert!(!check_value(b"M\xfcnchen", &type_def, false));
+        let type_def = Type::new("city", "München");
+        assert!(check_value("München".as_bytes(), &type_def, true));
+        assert!(check_value(b"\xff", &Type::new("any", ".*"), true));
+        assert!(check_value(b"12", &Type::new("int", r"\d+"), true));
+    }
+
+    #[test]
+    fn type_set_matches_like_check_value() {
+        let types = vec![
+            Type::new("string", ".*"),
+            Type::new("int", r"\d+"),
+            Type::new("city", r"M\xfcnchen"),
+            Type::new("word", r"\w+"),
+            Type::new("percent", "100|[1-9]?[0-9]"),
+            Type::from_native("float", Native::Float),
+        ];
+        let values: Vec<&[u8]> = vec![
+            b"12",
+            "١٢".as_bytes(),
+            b"M\xfcnchen",
+            "München".as_bytes(),
+            b"\xff",
+            b"x_1",
+            b"1000",
+            b"abc5",
+        ];
+        for raw_bytes in [false, true] {
+            let set = TypeSet::new(&types, raw_bytes);
+            assert!(set.set.is_some());
+            for value in &values {
+                let expected = types
+                    .iter()
+                    .map(|t| check_value(value, t, raw_bytes))
+                    .collect::<Vec<_>>();
+                assert_eq!(expected, set.check_all(&types, value));
+
+                let mut indices = (0..types.len()).collect::<Vec<_>>();
+                set.retain_matching(&types, &mut indices, value);
+                let mut retained = types.clone();
+                retain_matching_types(&mut retained, value, raw_bytes);
+                assert_eq!(
+                    retained,
+                    indices
+                        .iter()
+                        .map(|&i| types[i].clone())
+                        .collect::<Vec<_>>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn native_types_match_like_patterns() {
+        let int = Type::from_native("int", Native::Int);
+        let int_regex = Type::new("int", Native::Int.pattern());
+        assert_eq!(int_regex, int);
+        let values: Vec<&[u8]> = vec![b"12", b"-3", b"1.5", "١٢".as_bytes(), b"\xff1", b""];
+        for raw_bytes in [false, true] {
+            for value in &values {
+                assert_eq!(
+                    check_value(value, &int_regex, raw_bytes),
+                    check_value(value, &int, raw_bytes)
+                );
+            }
+        }
+        // Unicode digits are matched by the regex unless raw bytes are matched.
+        assert!(check_if_type_matches("١٢", &int));
+        assert!(!check_if_bytes_match("١٢".as_bytes(), &int));
+    }
+
+    #[test]
+    fn date_formats_decide_with_the_whole_column() {
+        let ambiguous = vec!["03/04/2021".to_owned(), "12/11/2021".to_owned()];
+        let names = |column: &[String]| {
+            get_matching_types(column, &date_formats())
+                .into_iter()
+                .map(|t| t.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["date(%d/%m/%Y)", "date(%m/%d/%Y)"], names(&ambiguous));
+        let mut day_first = ambiguous;
+        day_first.push("13/04/2021".to_owned());
+        assert_eq!(vec!["date(%d/%m/%Y)"], names(&day_first));
+        assert_eq!(
+            vec!["datetime(%Y-%m-%dT%H:%M:%S%:z)"],
+            names(&["2021-04-13T08:00:00+02:00".to_owned()])
+        );
+    }
+
+    #[test]
+    fn number_locales_decide_with_the_whole_column() {
+        let names = |column: &[&str]| {
+            let column = column.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            get_matching_types(&column, &number_locales())
+                .into_iter()
+                .map(|t| t.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec!["number(en)", "number(de)", "number(fr)"],
+            names(&["1,234", "12"])
+        );
+        assert_eq!(vec!["number(de)"], names(&["1,234", "1.234,5"]));
+        assert_eq!(vec!["number(fr)"], names(&["1 234,5"]));
+        assert_eq!(vec!["number(ch)"], names(&["1'234.5"]));
+
+        let mut types = date_formats().into_iter().chain(number_locales());
+        assert!(types.all(|t| t.parent.as_deref() == Some("string")));
+    }
+
+    #[test]
+    fn narrowest_width_type_first() {
+        let mut tl = TypeList::from(vec![
+            Type::new("string", ".*"),
+            Type::from_native("float", Native::Float).with_parent("string"),
+            Type::from_native("int", Native::Int).with_parent("float"),
+        ]);
+        for type_def in width_types() {
+            tl.add_type(type_def);
+        }
+        let best = |column: &[&str]| {
+            let column = column.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            let mut types = get_matching_types(&column, tl.get_types_vec());
+            tl.sort_by_specificity(&mut types);
+            types[0].name.clone()
+        };
+        assert_eq!("u8", best(&["0", "255"]));
+        assert_eq!("i8", best(&["-1", "127"]));
+        assert_eq!("u16", best(&["300"]));
+        assert_eq!("i32", best(&["-40000"]));
+        assert_eq!("u64", best(&["9223372036854775808"]));
+        assert_eq!("f32", best(&["1.5", "2"]));
+        assert_eq!("f64", best(&["0.123456789"]));
+        assert_eq!("float", best(&["3.14159265358979323846"]));
+    }
+
+    #[test]
+    fn get_nullable_type() {
+        let tl = TypeList::from(vec![Type::new("int", r"\d+")]);
+        let int = tl.get_type("int").unwrap();
+        assert!(!int.nullable);
+        let nullable = tl.get_type("int?").unwrap();
+        assert!(nullable.nullable);
+        assert_eq!("int?", nullable.to_string());
+        assert_eq!(None, tl.get_type("float?"));
+
+        let wind = tl.get_type("int(0..360)?").unwrap();
+        assert_eq!("int(0..360)?", wind.to_string());
+        assert_eq!(Some(String::from("int")), wind.parent);
+        assert!(wind.matcher.is_match("360"));
+        assert!(!wind.matcher.is_match("540"));
+        assert_eq!(None, tl.get_type("float(-90..90)").unwrap().parent);
+        assert_eq!(None, tl.get_type("int(360..0)"));
+
+        let u16 = tl.get_type("u16?").unwrap();
+        assert!(u16.nullable);
+        assert!(u16.matcher.is_match("65535"));
+        assert!(!u16.matcher.is_match("65536"));
+        assert_eq!(None, tl.get_type("u128"));
+    }
+
+    #[test]
+    fn sort_types_by_specificity() {
+        let tl = TypeList::from(vec![
+            Type::new("string", ".*"),
+            Type::new("int", r"\d+").with_parent("float"),
+            Type::new("float", r"\d+(\.\d+)?").with_parent("string"),
+            Type::new("bool", "[yn]"),
+            Type::new("loop", "x").with_parent("loop"),
+        ]);
+        assert_eq!(0, tl.specificity("string"));
+        assert_eq!(1, tl.specificity("float"));
+        assert_eq!(2, tl.specificity("int"));
+        assert_eq!(0, tl.specificity("unknown"));
+        assert_eq!(0, tl.specificity("loop"));
+        assert_eq!(
+            Err(Error::ParentCycle(String::from("loop"))),
+            tl.check_parents()
+        );
+
+        let mut types = tl.get_types_vec().clone();
+        types.retain(|t| t.name != "loop");
+        tl.sort_by_specificity(&mut types);
+        assert_eq!(
+            vec!["int", "float", "bool", "string"],
+            types.iter().map(|t| t.name.as_str()).collect::<Vec<_>>()
+        );
     }
 }